@@ -0,0 +1,965 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::mem;
+
+// NTSC CPU clock rate in Hz
+pub const CPU_FREQUENCY: f64 = 1789773.0;
+
+// Number of CPU cycles between each step of the frame counter
+const FRAME_STEP_1: u32 = 7457;
+const FRAME_STEP_2: u32 = 14913;
+const FRAME_STEP_3: u32 = 22371;
+const FRAME_STEP_4: u32 = 29829;
+const FRAME_STEP_5: u32 = 37281;
+
+pub struct APU {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: DMC,
+
+    // Number of cycles executed so far
+    cycle: u64,
+
+    // CPU cycles since the frame counter was last reset
+    frame_cycle: u32,
+
+    // $4017 - Frame Counter
+    frame_mode_5_step: bool,
+    frame_irq_inhibit: bool,
+    frame_irq: bool,
+
+    // Lookup tables for the non-linear mixer
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
+
+    // Output sample rate in Hz, no samples are produced while this is 0
+    sample_rate: u32,
+
+    // CPU cycles per output sample and how far we are into the current one
+    sample_period: f64,
+    sample_clock: f64,
+
+    // Running sum of the mixer output over the current sample
+    sample_sum: f32,
+    sample_count: u32,
+
+    // State of the high pass filter that removes the DC offset
+    filter_alpha: f32,
+    filter_prev_in: f32,
+    filter_prev_out: f32,
+
+    samples: Vec<f32>,
+}
+
+impl APU {
+    pub fn new(cart: Rc<RefCell<::cartridge::Cartridge>>) -> APU {
+        let mut pulse_table = [0.0; 31];
+        for (i, entry) in pulse_table.iter_mut().enumerate().skip(1) {
+            *entry = 95.52 / (8128.0 / (i as f32) + 100.0);
+        }
+
+        let mut tnd_table = [0.0; 203];
+        for (i, entry) in tnd_table.iter_mut().enumerate().skip(1) {
+            *entry = 163.67 / (24329.0 / (i as f32) + 100.0);
+        }
+
+        APU {
+            pulse1: Pulse::new(1),
+            pulse2: Pulse::new(2),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: DMC::new(cart),
+
+            cycle: 0,
+            frame_cycle: 0,
+
+            frame_mode_5_step: false,
+            frame_irq_inhibit: false,
+            frame_irq: false,
+
+            pulse_table: pulse_table,
+            tnd_table: tnd_table,
+
+            sample_rate: 0,
+            sample_period: 0.0,
+            sample_clock: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            filter_alpha: 0.0,
+            filter_prev_in: 0.0,
+            filter_prev_out: 0.0,
+
+            samples: Vec::new(),
+        }
+    }
+
+    // Start producing samples at the given rate, e.g. 44100 or 48000
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.sample_period = if sample_rate == 0 { 0.0 } else { CPU_FREQUENCY / sample_rate as f64 };
+        self.sample_clock = 0.0;
+        self.sample_sum = 0.0;
+        self.sample_count = 0;
+        self.samples.clear();
+
+        // First order high pass at 90Hz, like the one on the console's output
+        let rc = 1.0 / (2.0 * ::std::f32::consts::PI * 90.0);
+        let dt = 1.0 / sample_rate as f32;
+        self.filter_alpha = rc / (rc + dt);
+    }
+
+    // Hand over every sample produced since the last call
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        mem::take(&mut self.samples)
+    }
+
+    pub fn step(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.tick();
+        }
+    }
+
+    // Run one CPU cycle
+    fn tick(&mut self) {
+        self.cycle += 1;
+
+        // The triangle timer runs at the CPU rate, everything else at half of it
+        self.triangle.step_timer();
+        if self.cycle % 2 == 0 {
+            self.pulse1.step_timer();
+            self.pulse2.step_timer();
+            self.noise.step_timer();
+            self.dmc.step_timer();
+        }
+
+        self.step_frame_counter();
+
+        if self.sample_rate != 0 {
+            self.step_sample();
+        }
+    }
+
+    // Downsample from the CPU clock by averaging the mixer output over each output sample
+    fn step_sample(&mut self) {
+        self.sample_sum += self.output();
+        self.sample_count += 1;
+        self.sample_clock += 1.0;
+
+        if self.sample_clock >= self.sample_period {
+            self.sample_clock -= self.sample_period;
+
+            let sample = self.sample_sum / self.sample_count as f32;
+            self.sample_sum = 0.0;
+            self.sample_count = 0;
+
+            let out = self.filter_alpha * (self.filter_prev_out + sample - self.filter_prev_in);
+            self.filter_prev_in = sample;
+            self.filter_prev_out = out;
+
+            self.samples.push(out);
+        }
+    }
+
+    fn step_frame_counter(&mut self) {
+        self.frame_cycle += 1;
+
+        if self.frame_mode_5_step {
+            match self.frame_cycle {
+                FRAME_STEP_1 | FRAME_STEP_3 => {
+                    self.step_envelope();
+                }
+                FRAME_STEP_2 => {
+                    self.step_envelope();
+                    self.step_length_and_sweep();
+                }
+                FRAME_STEP_5 => {
+                    self.step_envelope();
+                    self.step_length_and_sweep();
+                    self.frame_cycle = 0;
+                }
+                _ => {}
+            }
+        } else {
+            match self.frame_cycle {
+                FRAME_STEP_1 | FRAME_STEP_3 => {
+                    self.step_envelope();
+                }
+                FRAME_STEP_2 => {
+                    self.step_envelope();
+                    self.step_length_and_sweep();
+                }
+                FRAME_STEP_4 => {
+                    self.step_envelope();
+                    self.step_length_and_sweep();
+                    if !self.frame_irq_inhibit {
+                        self.frame_irq = true;
+                    }
+                    self.frame_cycle = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Quarter frame
+    fn step_envelope(&mut self) {
+        self.pulse1.step_envelope();
+        self.pulse2.step_envelope();
+        self.triangle.step_counter();
+        self.noise.step_envelope();
+    }
+
+    // Half frame
+    fn step_length_and_sweep(&mut self) {
+        self.pulse1.step_length();
+        self.pulse1.step_sweep();
+        self.pulse2.step_length();
+        self.pulse2.step_sweep();
+        self.triangle.step_length();
+        self.noise.step_length();
+    }
+
+    // The mixed output of all five channels in the range 0.0 - 1.0
+    pub fn output(&self) -> f32 {
+        let p1 = self.pulse1.output();
+        let p2 = self.pulse2.output();
+        let t = self.triangle.output();
+        let n = self.noise.output();
+        let d = self.dmc.output();
+
+        let pulse_out = self.pulse_table[(p1 + p2) as usize];
+        let tnd_out = self.tnd_table[(3 * t as u16 + 2 * n as u16 + d as u16) as usize];
+
+        pulse_out + tnd_out
+    }
+
+    // True while either the frame counter or the DMC is requesting an interrupt
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    // $4015
+    pub fn read_status(&mut self) -> u8 {
+        let status =
+            ((self.pulse1.length_value > 0) as u8)   << 0 |
+            ((self.pulse2.length_value > 0) as u8)   << 1 |
+            ((self.triangle.length_value > 0) as u8) << 2 |
+            ((self.noise.length_value > 0) as u8)    << 3 |
+            ((self.dmc.current_length > 0) as u8)    << 4 |
+            (self.frame_irq as u8)                   << 6 |
+            (self.dmc.irq as u8)                     << 7;
+
+        // Reading the status clears the frame interrupt flag
+        self.frame_irq = false;
+
+        status
+    }
+
+    // $4015
+    pub fn write_control(&mut self, data: u8) {
+        self.pulse1.set_enabled(data & 0b00001 != 0);
+        self.pulse2.set_enabled(data & 0b00010 != 0);
+        self.triangle.set_enabled(data & 0b00100 != 0);
+        self.noise.set_enabled(data & 0b01000 != 0);
+        self.dmc.set_enabled(data & 0b10000 != 0);
+    }
+
+    // $4017
+    pub fn write_frame_counter(&mut self, data: u8) {
+        self.frame_mode_5_step = (data >> 7) & 0b1 == 1;
+        self.frame_irq_inhibit = (data >> 6) & 0b1 == 1;
+        if self.frame_irq_inhibit {
+            self.frame_irq = false;
+        }
+
+        self.frame_cycle = 0;
+
+        // Selecting the 5 step mode immediately clocks every unit
+        if self.frame_mode_5_step {
+            self.step_envelope();
+            self.step_length_and_sweep();
+        }
+    }
+}
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20,  2, 40,  4, 80,  6, 160,  8, 60, 10, 14, 12, 26, 14,
+    12,  16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const TRIANGLE_TABLE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10,  9,  8,  7,  6,  5,  4,  3,  2,  1,  0,
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+];
+
+// Noise periods in APU cycles (half the CPU rate)
+const NOISE_TABLE: [u16; 16] = [
+    2, 4, 8, 16, 32, 48, 64, 80, 101, 127, 190, 254, 381, 508, 1017, 2034,
+];
+
+// DMC rates in APU cycles (half the CPU rate)
+const DMC_TABLE: [u8; 16] = [
+    214, 190, 170, 160, 143, 127, 113, 107, 95, 80, 71, 64, 53, 42, 36, 27,
+];
+
+pub struct Pulse {
+    enabled: bool,
+
+    // Pulse 1 and 2 differ in how the sweep unit negates
+    channel: u8,
+
+    length_enabled: bool,
+    length_value: u8,
+
+    timer_period: u16,
+    timer_value: u16,
+
+    duty_mode: u8,
+    duty_value: u8,
+
+    sweep_reload: bool,
+    sweep_enabled: bool,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_period: u8,
+    sweep_value: u8,
+
+    envelope_enabled: bool,
+    envelope_loop: bool,
+    envelope_start: bool,
+    envelope_period: u8,
+    envelope_value: u8,
+    envelope_volume: u8,
+    constant_volume: u8,
+}
+
+impl Pulse {
+    fn new(channel: u8) -> Pulse {
+        Pulse {
+            enabled: false,
+            channel: channel,
+            length_enabled: false,
+            length_value: 0,
+            timer_period: 0,
+            timer_value: 0,
+            duty_mode: 0,
+            duty_value: 0,
+            sweep_reload: false,
+            sweep_enabled: false,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_period: 0,
+            sweep_value: 0,
+            envelope_enabled: false,
+            envelope_loop: false,
+            envelope_start: false,
+            envelope_period: 0,
+            envelope_value: 0,
+            envelope_volume: 0,
+            constant_volume: 0,
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_value = 0;
+        }
+    }
+
+    // $4000 / $4004
+    pub fn write_control(&mut self, data: u8) {
+        self.duty_mode        = (data >> 6) & 0b11;
+        self.length_enabled   = (data >> 5) & 0b1 == 0;
+        self.envelope_loop    = (data >> 5) & 0b1 == 1;
+        self.envelope_enabled = (data >> 4) & 0b1 == 0;
+        self.envelope_period  = data & 0x0F;
+        self.constant_volume  = data & 0x0F;
+        self.envelope_start   = true;
+    }
+
+    // $4001 / $4005
+    pub fn write_sweep(&mut self, data: u8) {
+        self.sweep_enabled = (data >> 7) & 0b1 == 1;
+        self.sweep_period  = ((data >> 4) & 0b111) + 1;
+        self.sweep_negate  = (data >> 3) & 0b1 == 1;
+        self.sweep_shift   = data & 0b111;
+        self.sweep_reload  = true;
+    }
+
+    // $4002 / $4006
+    pub fn write_timer_low(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0xFF00) | (data as u16);
+    }
+
+    // $4003 / $4007
+    pub fn write_timer_high(&mut self, data: u8) {
+        if self.enabled {
+            self.length_value = LENGTH_TABLE[(data >> 3) as usize];
+        }
+        self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+        self.envelope_start = true;
+        self.duty_value = 0;
+    }
+
+    fn step_timer(&mut self) {
+        if self.timer_value == 0 {
+            self.timer_value = self.timer_period;
+            self.duty_value = (self.duty_value + 1) % 8;
+        } else {
+            self.timer_value -= 1;
+        }
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_start {
+            self.envelope_volume = 15;
+            self.envelope_value = self.envelope_period;
+            self.envelope_start = false;
+        } else if self.envelope_value > 0 {
+            self.envelope_value -= 1;
+        } else {
+            if self.envelope_volume > 0 {
+                self.envelope_volume -= 1;
+            } else if self.envelope_loop {
+                self.envelope_volume = 15;
+            }
+            self.envelope_value = self.envelope_period;
+        }
+    }
+
+    fn step_length(&mut self) {
+        if self.length_enabled && self.length_value > 0 {
+            self.length_value -= 1;
+        }
+    }
+
+    fn step_sweep(&mut self) {
+        if self.sweep_reload {
+            if self.sweep_enabled && self.sweep_value == 0 {
+                self.sweep();
+            }
+            self.sweep_value = self.sweep_period;
+            self.sweep_reload = false;
+        } else if self.sweep_value > 0 {
+            self.sweep_value -= 1;
+        } else {
+            if self.sweep_enabled {
+                self.sweep();
+            }
+            self.sweep_value = self.sweep_period;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let delta = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            // Pulse 1 uses ones' complement, pulse 2 uses twos' complement
+            let delta = if self.channel == 1 { delta + 1 } else { delta };
+            self.timer_period.saturating_sub(delta)
+        } else {
+            self.timer_period + delta
+        }
+    }
+
+    fn sweep(&mut self) {
+        if self.sweep_shift == 0 {
+            return;
+        }
+        let target = self.sweep_target();
+        if self.timer_period >= 8 && target <= 0x7FF {
+            self.timer_period = target;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.length_value == 0 {
+            return 0;
+        }
+        if DUTY_TABLE[self.duty_mode as usize][self.duty_value as usize] == 0 {
+            return 0;
+        }
+        // Periods that are too short or would sweep out of range are muted
+        if self.timer_period < 8 || self.sweep_target() > 0x7FF {
+            return 0;
+        }
+        if self.envelope_enabled {
+            self.envelope_volume
+        } else {
+            self.constant_volume
+        }
+    }
+}
+
+pub struct Triangle {
+    enabled: bool,
+
+    length_enabled: bool,
+    length_value: u8,
+
+    timer_period: u16,
+    timer_value: u16,
+
+    duty_value: u8,
+
+    counter_period: u8,
+    counter_value: u8,
+    counter_reload: bool,
+}
+
+impl Triangle {
+    fn new() -> Triangle {
+        Triangle {
+            enabled: false,
+            length_enabled: false,
+            length_value: 0,
+            timer_period: 0,
+            timer_value: 0,
+            duty_value: 0,
+            counter_period: 0,
+            counter_value: 0,
+            counter_reload: false,
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_value = 0;
+        }
+    }
+
+    // $4008
+    pub fn write_control(&mut self, data: u8) {
+        self.length_enabled = (data >> 7) & 0b1 == 0;
+        self.counter_period = data & 0x7F;
+    }
+
+    // $400A
+    pub fn write_timer_low(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0xFF00) | (data as u16);
+    }
+
+    // $400B
+    pub fn write_timer_high(&mut self, data: u8) {
+        if self.enabled {
+            self.length_value = LENGTH_TABLE[(data >> 3) as usize];
+        }
+        // Unlike the pulse channels the timer keeps running, only the linear counter is reloaded
+        self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+        self.counter_reload = true;
+    }
+
+    fn step_timer(&mut self) {
+        if self.timer_value == 0 {
+            self.timer_value = self.timer_period;
+            // The sequencer only advances while both counters are non-zero
+            if self.length_value > 0 && self.counter_value > 0 {
+                self.duty_value = (self.duty_value + 1) % 32;
+            }
+        } else {
+            self.timer_value -= 1;
+        }
+    }
+
+    fn step_length(&mut self) {
+        if self.length_enabled && self.length_value > 0 {
+            self.length_value -= 1;
+        }
+    }
+
+    fn step_counter(&mut self) {
+        if self.counter_reload {
+            self.counter_value = self.counter_period;
+        } else if self.counter_value > 0 {
+            self.counter_value -= 1;
+        }
+        if self.length_enabled {
+            self.counter_reload = false;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.length_value == 0 || self.counter_value == 0 {
+            return 0;
+        }
+        TRIANGLE_TABLE[self.duty_value as usize]
+    }
+}
+
+pub struct Noise {
+    enabled: bool,
+
+    mode: bool,
+    shift_register: u16,
+
+    length_enabled: bool,
+    length_value: u8,
+
+    timer_period: u16,
+    timer_value: u16,
+
+    envelope_enabled: bool,
+    envelope_loop: bool,
+    envelope_start: bool,
+    envelope_period: u8,
+    envelope_value: u8,
+    envelope_volume: u8,
+    constant_volume: u8,
+}
+
+impl Noise {
+    fn new() -> Noise {
+        Noise {
+            enabled: false,
+            mode: false,
+            shift_register: 1,
+            length_enabled: false,
+            length_value: 0,
+            timer_period: 0,
+            timer_value: 0,
+            envelope_enabled: false,
+            envelope_loop: false,
+            envelope_start: false,
+            envelope_period: 0,
+            envelope_value: 0,
+            envelope_volume: 0,
+            constant_volume: 0,
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_value = 0;
+        }
+    }
+
+    // $400C
+    pub fn write_control(&mut self, data: u8) {
+        self.length_enabled   = (data >> 5) & 0b1 == 0;
+        self.envelope_loop    = (data >> 5) & 0b1 == 1;
+        self.envelope_enabled = (data >> 4) & 0b1 == 0;
+        self.envelope_period  = data & 0x0F;
+        self.constant_volume  = data & 0x0F;
+        self.envelope_start   = true;
+    }
+
+    // $400E
+    pub fn write_period(&mut self, data: u8) {
+        self.mode = (data >> 7) & 0b1 == 1;
+        self.timer_period = NOISE_TABLE[(data & 0x0F) as usize];
+    }
+
+    // $400F
+    pub fn write_length(&mut self, data: u8) {
+        if self.enabled {
+            self.length_value = LENGTH_TABLE[(data >> 3) as usize];
+        }
+        self.envelope_start = true;
+    }
+
+    fn step_timer(&mut self) {
+        if self.timer_value == 0 {
+            self.timer_value = self.timer_period;
+            let shift = if self.mode { 6 } else { 1 };
+            let b1 = self.shift_register & 1;
+            let b2 = (self.shift_register >> shift) & 1;
+            self.shift_register >>= 1;
+            self.shift_register |= (b1 ^ b2) << 14;
+        } else {
+            self.timer_value -= 1;
+        }
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_start {
+            self.envelope_volume = 15;
+            self.envelope_value = self.envelope_period;
+            self.envelope_start = false;
+        } else if self.envelope_value > 0 {
+            self.envelope_value -= 1;
+        } else {
+            if self.envelope_volume > 0 {
+                self.envelope_volume -= 1;
+            } else if self.envelope_loop {
+                self.envelope_volume = 15;
+            }
+            self.envelope_value = self.envelope_period;
+        }
+    }
+
+    fn step_length(&mut self) {
+        if self.length_enabled && self.length_value > 0 {
+            self.length_value -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.enabled || self.length_value == 0 || self.shift_register & 1 == 1 {
+            return 0;
+        }
+        if self.envelope_enabled {
+            self.envelope_volume
+        } else {
+            self.constant_volume
+        }
+    }
+}
+
+pub struct DMC {
+    enabled: bool,
+
+    // Samples are fetched straight out of cartridge space
+    cart: Rc<RefCell<::cartridge::Cartridge>>,
+
+    value: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    current_length: u16,
+
+    shift_register: u8,
+    bit_count: u8,
+
+    tick_period: u8,
+    tick_value: u8,
+
+    loop_flag: bool,
+    irq_enabled: bool,
+    irq: bool,
+}
+
+impl DMC {
+    fn new(cart: Rc<RefCell<::cartridge::Cartridge>>) -> DMC {
+        DMC {
+            enabled: false,
+            cart: cart,
+            value: 0,
+            sample_address: 0,
+            sample_length: 0,
+            current_address: 0,
+            current_length: 0,
+            shift_register: 0,
+            bit_count: 0,
+            tick_period: 0,
+            tick_value: 0,
+            loop_flag: false,
+            irq_enabled: false,
+            irq: false,
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.irq = false;
+        if !enabled {
+            self.current_length = 0;
+        } else if self.current_length == 0 {
+            self.restart();
+        }
+    }
+
+    // $4010
+    pub fn write_control(&mut self, data: u8) {
+        self.irq_enabled = (data >> 7) & 0b1 == 1;
+        self.loop_flag   = (data >> 6) & 0b1 == 1;
+        self.tick_period = DMC_TABLE[(data & 0x0F) as usize];
+        if !self.irq_enabled {
+            self.irq = false;
+        }
+    }
+
+    // $4011
+    pub fn write_value(&mut self, data: u8) {
+        self.value = data & 0x7F;
+    }
+
+    // $4012
+    pub fn write_address(&mut self, data: u8) {
+        // Address = 11AAAAAA.AA000000
+        self.sample_address = 0xC000 | ((data as u16) << 6);
+    }
+
+    // $4013
+    pub fn write_length(&mut self, data: u8) {
+        // Length = LLLL.LLLL0001
+        self.sample_length = ((data as u16) << 4) | 1;
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.current_length = self.sample_length;
+    }
+
+    fn read_sample(&self, address: u16) -> u8 {
+        self.cart.borrow().read(address)
+    }
+
+    fn step_timer(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.step_reader();
+        if self.tick_value == 0 {
+            self.tick_value = self.tick_period;
+            self.step_shifter();
+        } else {
+            self.tick_value -= 1;
+        }
+    }
+
+    fn step_reader(&mut self) {
+        if self.current_length > 0 && self.bit_count == 0 {
+            self.shift_register = self.read_sample(self.current_address);
+            self.bit_count = 8;
+
+            // The address wraps around to $8000 rather than $0000
+            self.current_address = if self.current_address == 0xFFFF { 0x8000 } else { self.current_address + 1 };
+            self.current_length -= 1;
+
+            if self.current_length == 0 {
+                if self.loop_flag {
+                    self.restart();
+                } else if self.irq_enabled {
+                    self.irq = true;
+                }
+            }
+        }
+    }
+
+    fn step_shifter(&mut self) {
+        if self.bit_count == 0 {
+            return;
+        }
+        if self.shift_register & 1 == 1 {
+            if self.value <= 125 {
+                self.value += 2;
+            }
+        } else if self.value >= 2 {
+            self.value -= 2;
+        }
+        self.shift_register >>= 1;
+        self.bit_count -= 1;
+    }
+
+    fn output(&self) -> u8 {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    fn apu() -> APU {
+        let cartridge = ::cartridge::Cartridge::new(rominfo(0, 0, 0x4000, 0x2000)).unwrap();
+        APU::new(Rc::new(RefCell::new(cartridge)))
+    }
+
+    fn run(apu: &mut APU, cycles: u32) {
+        for _ in 0..cycles {
+            apu.tick();
+        }
+    }
+
+    #[test]
+    fn four_step_mode_raises_an_irq_at_the_end_of_the_frame() {
+        let mut apu = apu();
+        run(&mut apu, FRAME_STEP_4 - 1);
+        assert!(!apu.irq());
+        run(&mut apu, 1);
+        assert!(apu.irq());
+
+        // Reading $4015 acknowledges it
+        assert_eq!(apu.read_status() & 0x40, 0x40);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn frame_irq_can_be_inhibited() {
+        let mut apu = apu();
+        apu.write_frame_counter(0x40);
+        run(&mut apu, FRAME_STEP_4);
+        assert!(!apu.irq());
+
+        // Setting the inhibit flag also clears a pending interrupt
+        apu.write_frame_counter(0x00);
+        run(&mut apu, FRAME_STEP_4);
+        assert!(apu.irq());
+        apu.write_frame_counter(0x40);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn five_step_mode_never_raises_an_irq() {
+        let mut apu = apu();
+        apu.write_frame_counter(0x80);
+        run(&mut apu, FRAME_STEP_5 * 2);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn length_counter_counts_down_on_half_frames() {
+        let mut apu = apu();
+        apu.write_control(0x01);
+        apu.pulse1.write_control(0x00);
+        apu.pulse1.write_timer_high(0x00);
+        assert_eq!(apu.pulse1.length_value, 10);
+        assert_eq!(apu.read_status() & 0x01, 0x01);
+
+        run(&mut apu, FRAME_STEP_2 - 1);
+        assert_eq!(apu.pulse1.length_value, 10);
+        run(&mut apu, 1);
+        assert_eq!(apu.pulse1.length_value, 9);
+        run(&mut apu, FRAME_STEP_4 - FRAME_STEP_2);
+        assert_eq!(apu.pulse1.length_value, 8);
+
+        // Five step mode clocks it straight away when selected
+        apu.write_frame_counter(0x80);
+        assert_eq!(apu.pulse1.length_value, 7);
+    }
+
+    #[test]
+    fn length_counter_halts_and_clears_when_disabled() {
+        let mut apu = apu();
+        apu.write_control(0x01);
+        apu.pulse1.write_control(0x20);
+        apu.pulse1.write_timer_high(0x00);
+        run(&mut apu, FRAME_STEP_4);
+        assert_eq!(apu.pulse1.length_value, 10);
+
+        apu.write_control(0x00);
+        assert_eq!(apu.read_status() & 0x01, 0x00);
+
+        // Loading it while disabled does nothing
+        apu.pulse1.write_timer_high(0x00);
+        assert_eq!(apu.pulse1.length_value, 0);
+    }
+
+    #[test]
+    fn triangle_timer_keeps_running_through_a_period_write() {
+        let mut apu = apu();
+        apu.write_control(0x04);
+        apu.triangle.write_timer_low(0x10);
+        apu.triangle.write_timer_high(0x00);
+        run(&mut apu, 5);
+        let timer = apu.triangle.timer_value;
+
+        apu.triangle.write_timer_high(0x08);
+        assert_eq!(apu.triangle.timer_value, timer);
+        assert_eq!(apu.triangle.length_value, LENGTH_TABLE[1]);
+        assert!(apu.triangle.counter_reload);
+    }
+}
@@ -0,0 +1,79 @@
+// Dumps the disassembly of a 16k PRG-ROM bank, e.g.
+// C000  4C F5 C5  JMP $C5F5
+
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::disasm::disassemble;
+use nes::ines::INesInfo;
+
+const BANK_SIZE: usize = 0x4000;
+
+fn usage(program: &String) -> ! {
+    println!("usage: {} <rom file> [bank] [origin]", program);
+    println!();
+    println!("bank is the 16k PRG-ROM bank to dump, 0 unless given. It's disassembled as if it were");
+    println!("mapped at origin (in hex), which defaults to $C000 for the last bank and $8000 otherwise.");
+    std::process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 4 {
+        usage(&args[0]);
+    }
+
+    let rominfo = match INesInfo::new(&args[1]) {
+        Ok(rominfo) => rominfo,
+        Err(err) => {
+            println!("Couldn't load {}: {}", args[1], err);
+            std::process::exit(1);
+        }
+    };
+
+    let banks = rominfo.prgrom.len() / BANK_SIZE;
+    if banks == 0 {
+        println!("{} has no whole 16k PRG-ROM banks to disassemble", args[1]);
+        std::process::exit(1);
+    }
+
+    let bank = match args.get(2).map(|bank| bank.parse::<usize>()) {
+        None => 0,
+        Some(Ok(bank)) if bank < banks => bank,
+        _ => {
+            println!("The ROM has {} PRG-ROM banks, numbered from 0", banks);
+            std::process::exit(1);
+        }
+    };
+
+    let origin = match args.get(3).map(|origin| u16::from_str_radix(origin.trim_start_matches('$'), 16)) {
+        None if bank == banks - 1 => 0xC000,
+        None => 0x8000,
+        Some(Ok(origin)) => origin,
+        Some(Err(_)) => usage(&args[0]),
+    };
+
+    let mut ram = FlatRam::new();
+    ram.load(&rominfo.prgrom[bank * BANK_SIZE..(bank + 1) * BANK_SIZE], origin);
+
+    // Anything mapped past $FFFF is cut off
+    let end = (origin as usize + BANK_SIZE).min(0x10000);
+    let mut address = origin as usize;
+    while address < end {
+        let instruction = disassemble(&ram, address as u16);
+
+        // An instruction whose operand runs past the end of the bank is only data
+        if address + instruction.len() as usize > end {
+            let tail = &ram.data[address..end];
+            let bytes: Vec<String> = tail.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let values: Vec<String> = tail.iter().map(|byte| format!("${:02X}", byte)).collect();
+            println!("{:04X}  {:<8}  .db {}", address, bytes.join(" "), values.join(", "));
+            break;
+        }
+
+        let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:04X}  {:<8} {}{}", address, bytes.join(" "), if instruction.unofficial { '*' } else { ' ' }, instruction);
+
+        address += instruction.len() as usize;
+    }
+}
@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use apu::APU;
+use cartridge::Cartridge;
+use cpu::{CPU, Interrupt};
+use ines::{INesInfo, RomError};
+use memory::CPUMemoryMap;
+use ppu::PPU;
+
+// Headless runner for blargg's test ROMs. They report through PRG-RAM:
+//   $6000          status, $80 while running, $81 when the reset button should be pressed and
+//                  the result code once finished (0 means passed)
+//   $6001 - $6003  $DE $B0 $61 once the values at $6000 can be trusted
+//   $6004 -        zero terminated ASCII text describing the result
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const CYCLES_PER_FRAME: u64 = 29781;
+
+// The tests want reset pressed at least 100ms after they ask for it
+const RESET_DELAY_FRAMES: u64 = 10;
+
+pub struct TestResult {
+    pub status: u8,
+    pub message: String,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.status == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum TestError {
+    Rom(RomError),
+    // The ROM didn't finish in time, holds whatever message it had written so far
+    Timeout(String),
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TestError::Rom(ref err) => write!(f, "{}", err),
+            TestError::Timeout(ref message) => write!(f, "test didn't finish: {}", message),
+        }
+    }
+}
+
+impl Error for TestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TestError::Rom(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<RomError> for TestError {
+    fn from(err: RomError) -> TestError {
+        TestError::Rom(err)
+    }
+}
+
+// Runs the test ROM at path for up to timeout_frames frames of emulated time
+pub fn run<P: AsRef<Path>>(path: P, timeout_frames: u64) -> Result<TestResult, TestError> {
+    let rominfo = INesInfo::new(path)?;
+    let cartridge = Rc::new(RefCell::new(Cartridge::new(rominfo)?));
+    let ppu = PPU::new(cartridge.clone());
+    let apu = APU::new(cartridge.clone());
+    let mut cpu = CPU::new(CPUMemoryMap::new(cartridge, ppu, apu));
+
+    let timeout = timeout_frames * CYCLES_PER_FRAME;
+    let mut cycles: u64 = 0;
+    let mut reset_at: Option<u64> = None;
+
+    while cycles < timeout {
+        let int = if cpu.memory.ppu.nmi { cpu.memory.ppu.nmi = false; Interrupt::IntNMI } else { Interrupt::IntNone };
+        cycles += cpu.step(int) as u64;
+
+        if !has_signature(&cpu.memory) {
+            continue;
+        }
+
+        match cpu.memory.read(0x6000) {
+            STATUS_RUNNING => {},
+            STATUS_NEEDS_RESET => {
+                match reset_at {
+                    None => reset_at = Some(cycles + RESET_DELAY_FRAMES * CYCLES_PER_FRAME),
+                    Some(at) if cycles >= at => {
+                        reset_at = None;
+                        cpu.reset();
+                    },
+                    _ => {},
+                }
+            },
+            status => {
+                return Ok(TestResult {
+                    status: status,
+                    message: read_message(&cpu.memory),
+                });
+            },
+        }
+    }
+
+    Err(TestError::Timeout(read_message(&cpu.memory)))
+}
+
+fn has_signature(memory: &CPUMemoryMap) -> bool {
+    (0..3).all(|i| memory.read(0x6001 + i as u16) == SIGNATURE[i])
+}
+
+fn read_message(memory: &CPUMemoryMap) -> String {
+    let mut message = String::new();
+
+    for address in 0x6004..0x8000 {
+        let byte = memory.read(address);
+        if byte == 0 {
+            break;
+        }
+        message.push(byte as char);
+    }
+
+    message
+}
@@ -0,0 +1,74 @@
+// Everything the CPU can see. The NES's CPUMemoryMap is one, FlatRam lets the CPU run on plain
+// memory for tests and other 6502 projects.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, data: u8, address: u16);
+
+    // Reads without side effects, for debugging output
+    fn peek(&self, address: u16) -> u8;
+
+    // Called once per CPU cycle before the cycle's memory access
+    fn tick(&mut self) {}
+
+    // State of the IRQ line
+    fn irq(&self) -> bool {
+        false
+    }
+}
+
+// The parts of the NES around its CPU that the CPU has to work with. CPUMemoryMap fills these in,
+// buses with nothing but memory can take the defaults.
+pub trait NesBus: Bus {
+    // Called when the CPU is reset
+    fn reset(&mut self) {}
+
+    // The page to copy into OAM if the last write started an OAM DMA
+    fn take_dma_page(&mut self) -> Option<u8> {
+        None
+    }
+
+    // Where the video hardware is as (frame, scanline, dot), for trace logs
+    fn video_position(&self) -> (u64, u16, u16) {
+        (0, 0, 0)
+    }
+}
+
+// 64k of RAM and nothing else
+pub struct FlatRam {
+    pub data: Box<[u8]>,
+}
+
+impl FlatRam {
+    pub fn new() -> FlatRam {
+        FlatRam { data: vec![0; 0x10000].into_boxed_slice() }
+    }
+
+    // Copies data into memory starting at address
+    pub fn load(&mut self, data: &[u8], address: u16) {
+        let start = address as usize;
+        let end = (start + data.len()).min(0x10000);
+        self.data[start..end].copy_from_slice(&data[..end - start]);
+    }
+}
+
+impl Default for FlatRam {
+    fn default() -> FlatRam {
+        FlatRam::new()
+    }
+}
+
+impl Bus for FlatRam {
+    fn read(&mut self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, data: u8, address: u16) {
+        self.data[address as usize] = data;
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+}
+
+impl NesBus for FlatRam {}
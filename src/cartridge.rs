@@ -1,19 +1,156 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use mapper::Mapper;
+
 pub struct Cartridge {
-    pub ram: Box<[u8]>,
-    pub prgrom: Vec<u8>,
-    pub chrrom: Vec<u8>
+    pub mapper: Box<dyn Mapper>,
+    // Nametables 2 and 3 on four-screen boards, empty otherwise
+    pub vram: Box<[u8]>,
+
+    // PRG-RAM is battery backed and should survive between runs
+    pub battery: bool,
+    // Contents of PRG-RAM as of the last load or save, so unchanged RAM isn't rewritten
+    saved_ram: Vec<u8>,
 }
 
 impl Cartridge {
-    pub fn new(rominfo: ::ines::INesInfo) -> Cartridge {
-        Cartridge {prgrom: rominfo.prgrom, ram: Box::new([0;0x2000]), chrrom: rominfo.chrrom}
+    pub fn new(rominfo: ::ines::INesInfo) -> Result<Cartridge, ::ines::RomError> {
+        let vram = if rominfo.mirroring == ::mapper::Mirroring::FourScreen { vec![0; 0x800] } else { Vec::new() };
+        let battery = rominfo.battery;
+        let mapper = ::mapper::new(rominfo)?;
+        let saved_ram = mapper.prg_ram().to_vec();
+        Ok(Cartridge {mapper: mapper, vram: vram.into_boxed_slice(), battery: battery, saved_ram: saved_ram})
+    }
+
+    // Restore PRG-RAM from a save file. Carts without a battery have nothing to restore.
+    pub fn load_ram<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        if !self.battery {
+            return Ok(());
+        }
+
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        let ram = self.mapper.prg_ram_mut();
+        let len = ::std::cmp::min(ram.len(), data.len());
+        ram[..len].copy_from_slice(&data[..len]);
+
+        self.saved_ram = ram.to_vec();
+        Ok(())
     }
 
-    pub fn read(&self, addr: u16) {
+    // Write battery backed PRG-RAM out to a save file if it has changed since it was last loaded
+    // or saved
+    pub fn save_ram<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        if !self.battery || self.mapper.prg_ram() == &self.saved_ram[..] {
+            return Ok(());
+        }
 
+        File::create(path)?.write_all(self.mapper.prg_ram())?;
+
+        self.saved_ram = self.mapper.prg_ram().to_vec();
+        Ok(())
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.mapper.cpu_read(addr)
     }
 
     pub fn write(&mut self, data: u8, addr: u16) {
+        self.mapper.cpu_write(data, addr)
+    }
+
+    pub fn ppu_read(&self, addr: u16) -> u8 {
+        self.mapper.ppu_read(addr)
+    }
+
+    pub fn ppu_write(&mut self, data: u8, addr: u16) {
+        self.mapper.ppu_write(data, addr)
+    }
+
+    pub fn mirroring(&self) -> ::mapper::Mirroring {
+        self.mapper.mirroring()
+    }
+
+    pub fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+
+    pub fn watch_ppu_address(&mut self, addr: u16) {
+        self.mapper.watch_ppu_address(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use mapper::tests::rominfo;
+
+    // A save file in the temp directory that's gone before and after the test
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let path = ::std::env::temp_dir().join(format!("nes-{}-{}.sav", name, ::std::process::id()));
+            let _ = fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn cartridge(flags6: u8) -> Cartridge {
+        Cartridge::new(rominfo(0, flags6, 0x4000, 0x2000)).unwrap()
+    }
+
+    #[test]
+    fn saved_ram_loads_back() {
+        let file = TempFile::new("round-trip");
+        let mut cartridge = cartridge(0x02);
+        cartridge.write(0x12, 0x6000);
+        cartridge.write(0x34, 0x7FFF);
+        cartridge.save_ram(&file.0).unwrap();
+        assert_eq!(fs::metadata(&file.0).unwrap().len(), 0x2000);
+
+        let mut loaded = self::cartridge(0x02);
+        loaded.load_ram(&file.0).unwrap();
+        assert_eq!(loaded.read(0x6000), 0x12);
+        assert_eq!(loaded.read(0x7FFF), 0x34);
+    }
+
+    #[test]
+    fn carts_without_a_battery_never_touch_the_save_file() {
+        let file = TempFile::new("no-battery");
+        let mut cartridge = cartridge(0x00);
+        cartridge.write(0x12, 0x6000);
+        cartridge.save_ram(&file.0).unwrap();
+        assert!(!file.0.exists());
+
+        fs::write(&file.0, [0x34]).unwrap();
+        cartridge.load_ram(&file.0).unwrap();
+        assert_eq!(cartridge.read(0x6000), 0x12);
+    }
+
+    #[test]
+    fn unchanged_ram_isnt_written() {
+        let file = TempFile::new("unchanged");
+        let mut cartridge = cartridge(0x02);
+        cartridge.save_ram(&file.0).unwrap();
+        assert!(!file.0.exists());
 
+        cartridge.write(0x12, 0x6000);
+        cartridge.save_ram(&file.0).unwrap();
+        fs::remove_file(&file.0).unwrap();
+        cartridge.save_ram(&file.0).unwrap();
+        assert!(!file.0.exists());
     }
 }
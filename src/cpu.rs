@@ -1,13 +1,22 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
-pub struct CPU {
-    pub memory: ::memory::CPUMemoryMap,
+use std::mem;
+
+use bus::NesBus;
+use disasm::disassemble;
+use memory::CPUMemoryMap;
+use trace::Tracer;
+
+pub struct CPU<M: NesBus = CPUMemoryMap> {
+    pub memory: M,
+    variant: Variant,
+
     // Number of cycles executed so far
     cycles: u64,
 
     // Cycles for this step
-    this_cycles: u8,
+    this_cycles: u16,
 
     // Program Counter
     pc: u16,
@@ -26,14 +35,63 @@ pub struct CPU {
     c: u8,  // Carry
     z: u8,  // Zero
     i: u8,  // Interrupt Mask
-    d: u8,  // Decimal (NEVER USED)
+    d: u8,  // Decimal (only used by the NMOS 6502 variant)
     b: u8,  // This doesn't do anything
     u: u8,  // Unused
     v: u8,  // Overflow
     n: u8,  // Negative
 
     interrupt: u8,
-    // stall: u32
+
+    // Cycles of this step spent halted for OAM DMA
+    stall: u16,
+
+    tracer: Option<Tracer>,
+}
+
+// Which chip to emulate. The NES's 2A03 is a 6502 with decimal mode cut out, the D flag can
+// still be set but ADC and SBC ignore it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Ricoh2A03,
+    Nmos6502,
+}
+
+// A snapshot of the registers, for debuggers, tests and save states
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpuState {
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    // Status register, NV-BDIZC
+    pub p: u8,
+    // Cycles executed since power on
+    pub cycles: u64,
+}
+
+pub const FLAG_CARRY: u8 = 1 << 0;
+pub const FLAG_ZERO: u8 = 1 << 1;
+pub const FLAG_INTERRUPT: u8 = 1 << 2;
+pub const FLAG_DECIMAL: u8 = 1 << 3;
+pub const FLAG_BREAK: u8 = 1 << 4;
+pub const FLAG_UNUSED: u8 = 1 << 5;
+pub const FLAG_OVERFLOW: u8 = 1 << 6;
+pub const FLAG_NEGATIVE: u8 = 1 << 7;
+
+impl CpuState {
+    pub fn flag(&self, flag: u8) -> bool {
+        self.p & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
 }
 
 pub enum Interrupt {
@@ -47,18 +105,44 @@ fn pages_differ(one: u16, two: u16) -> bool {
     one & 0xFF00 != two & 0xFF00
 }
 
+// Opcodes that aren't part of the documented 6502 instruction set
+pub fn is_unofficial(opcode: u8) -> bool {
+    match INSTRUCTIONS[opcode as usize].str_name {
+        "NOP" => opcode != 0xea,
+        "SBC" => opcode == 0xeb,
+        "SLO" | "RLA" | "SRE" | "RRA" | "SAX" | "LAX" | "DCP" | "ISC" | "ANC" | "ALR" | "ARR" |
+        "XAA" | "AXS" | "AHX" | "SHX" | "SHY" | "TAS" | "LAS" | "KIL" => true,
+        _ => false,
+    }
+}
+
+// Whether an instruction writes to its operand. Indexed addressing always spends a cycle fixing
+// up the high byte for these, reads skip it when no page is crossed.
+fn writes_memory(opcode: u8) -> bool {
+    match INSTRUCTIONS[opcode as usize].str_name {
+        "STA" | "STX" | "STY" | "SAX" | "SHX" | "SHY" | "AHX" | "TAS" |
+        "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" |
+        "SLO" | "RLA" | "SRE" | "RRA" | "DCP" | "ISC" => true,
+        _ => false,
+    }
+}
+
+// The NES's CPU, a 2A03 on the NES memory map
 impl CPU {
-    pub fn new(mem: ::memory::CPUMemoryMap) -> CPU {
-        let pcval = {
-            ((mem.read(0xFFFD) as u16) << 8) + (mem.read(0xFFFC) as u16)
-        };
+    pub fn new(mem: CPUMemoryMap) -> CPU {
+        CPU::with_variant(mem, Variant::Ricoh2A03)
+    }
+}
 
-        CPU {
+impl<M: NesBus> CPU<M> {
+    pub fn with_variant(mem: M, variant: Variant) -> CPU<M> {
+        let mut cpu = CPU {
             memory: mem,
+            variant: variant,
             cycles: 0,
             this_cycles: 0,
-            pc: pcval,
-            sp: 0xfd,
+            pc: 0,
+            sp: 0,
             a: 0,
             x: 0,
             y: 0,
@@ -67,17 +151,129 @@ impl CPU {
             i: 0,
             d: 0,
             b: 0,
-            u: 0,
+            u: 1,
             v: 0,
             n: 0,
             interrupt: 0,
-            //stall: 0
+            stall: 0,
+            tracer: None,
+        };
+
+        cpu.reset();
+        cpu.this_cycles = 0;
+        cpu
+    }
+
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            sp: self.sp,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: self.flags(),
+            cycles: self.cycles,
         }
     }
 
+    pub fn set_state(&mut self, state: &CpuState) {
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.set_flags(state.p);
+        self.cycles = state.cycles;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Pressing the reset button. The registers keep their values, the stack pointer moves down 3
+    // bytes without anything being written and interrupts are disabled. Takes 7 cycles.
+    pub fn reset(&mut self) {
+        let pc = self.pc;
+        self.read(pc);
+        self.read(pc);
+
+        for _ in 0..3 {
+            let sp = self.sp;
+            self.read(sp as u16 + 0x100);
+            self.sp = sp.wrapping_sub(1);
+        }
+
+        self.i = 1;
+        self.pc = self.read16(0xFFFC);
+
+        self.memory.reset();
+    }
+
+    // Every memory access takes one cycle, during which the rest of the system runs
+    fn read(&mut self, address: u16) -> u8 {
+        self.tick();
+        self.memory.read(address)
+    }
+
+    fn read16(&mut self, address: u16) -> u16 {
+        let lo = self.read(address) as u16;
+        let hi = self.read(address.wrapping_add(1)) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn write(&mut self, data: u8, address: u16) {
+        self.tick();
+        self.memory.write(data, address);
+
+        if let Some(page) = self.memory.take_dma_page() {
+            self.oam_dma(page);
+        }
+    }
+
+    // Writing to $4014 halts the CPU while 256 bytes are copied from the page into OAM. That takes
+    // a cycle for the write to finish, another if the copy has to wait for an even cycle to start,
+    // then a read and a write per byte for 513 or 514 cycles in all.
+    fn oam_dma(&mut self, page: u8) {
+        let start = self.this_cycles;
+
+        self.tick();
+        if self.cycles % 2 == 1 {
+            self.tick();
+        }
+
+        let address = (page as u16) << 8;
+        for offset in 0..256 {
+            let data = self.read(address + offset);
+            self.write(data, 0x2004);
+        }
+
+        self.stall += self.this_cycles - start;
+    }
+
+    // Cycles the last step spent halted for OAM DMA, included in what step() returned
+    pub fn stall(&self) -> u16 {
+        self.stall
+    }
+
+    fn tick(&mut self) {
+        self.memory.tick();
+        self.cycles += 1;
+        self.this_cycles += 1;
+    }
+
     fn push(&mut self, data: u8) {
-        self.memory.write(data, self.sp as u16 + 0x100);
-        self.sp -= 1;
+        let sp = self.sp;
+        self.write(data, sp as u16 + 0x100);
+        self.sp = sp.wrapping_sub(1);
     }
 
     fn push16(&mut self, data: u16) {
@@ -86,8 +282,15 @@ impl CPU {
     }
 
     fn pop(&mut self) -> u8 {
-        self.sp += 1;
-        self.memory.read(self.sp as u16 + 0x100)
+        self.sp = self.sp.wrapping_add(1);
+        let sp = self.sp;
+        self.read(sp as u16 + 0x100)
+    }
+
+    // Stack pulls spend a cycle reading the stack before sp is incremented
+    fn dummy_stack_read(&mut self) {
+        let sp = self.sp;
+        self.read(sp as u16 + 0x100);
     }
 
     fn pop16(&mut self) -> u16 {
@@ -119,67 +322,80 @@ impl CPU {
         status
     }
 
+    // Describes the instruction at pc in the same format as Nintendulator's trace logs, e.g.
+    // C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+    pub fn trace(&self) -> String {
+        let instruction = disassemble(&self.memory, self.pc);
+
+        let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        let annotation = instruction.annotation(&self.memory, self.x, self.y);
+        let disassembly = if annotation.is_empty() {
+            instruction.to_string()
+        } else {
+            format!("{} {}", instruction, annotation)
+        };
+
+        let (_, scanline, dot) = self.memory.video_position();
+
+        format!("{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+                self.pc, bytes.join(" "), if instruction.unofficial { '*' } else { ' ' }, disassembly,
+                self.a, self.x, self.y, self.flags(), self.sp, scanline, dot, self.cycles)
+    }
+
+    // Starts logging every instruction to tracer, or stops with None. Returns the old tracer.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        mem::replace(&mut self.tracer, tracer)
+    }
+
+    pub fn tracer(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    fn log_trace(&mut self) {
+        let (frame, _, _) = self.memory.video_position();
+        let pc = self.pc;
+        if !self.tracer.as_ref().map_or(false, |tracer| tracer.wants(pc, frame)) {
+            return;
+        }
 
-    pub fn step(&mut self, int: Interrupt) -> u8 {
+        let line = self.trace();
+        if let Err(err) = self.tracer.as_mut().unwrap().log(&line) {
+            println!("Couldn't write the trace log, tracing stopped: {}", err);
+            self.tracer = None;
+        }
+    }
+
+    // Runs one instruction, or services an interrupt and stops with pc at the start of the
+    // handler, so a debugger gets to look at its first instruction. The PPU and APU are kept in
+    // step on every memory access. Returns the cycles taken.
+    pub fn step(&mut self, int: Interrupt) -> u16 {
         self.this_cycles = 0;
+        self.stall = 0;
 
-        match int {
-            Interrupt::IntNMI => self.nmi(),
+        let serviced = match int {
+            Interrupt::IntNMI => {
+                self.nmi();
+                true
+            },
             Interrupt::IntIRQ => self.irq(),
-            Interrupt::IntNone => {},
+            // The IRQ line is level triggered, so keep servicing it for as long as it's held
+            Interrupt::IntNone => self.memory.irq() && self.irq(),
+        };
+
+        if serviced {
+            return self.this_cycles;
+        }
+
+        if self.tracer.is_some() {
+            self.log_trace();
         }
 
         let (fun, address, addr_mode, size, str_name) = {
-            let opcode = self.memory.read(self.pc);
+            let pc = self.pc;
+            let opcode = self.read(pc);
             let instruction = &INSTRUCTIONS[opcode as usize];
-            let mut page_crossed = false;
-            let address:u16 = match instruction.addr_mode {
-                AddressingMode::Implicit => 0,
-                AddressingMode::Accumulator => 0,
-                AddressingMode::Immediate => self.pc + 1,
-                AddressingMode::ZeroPage => self.memory.read(self.pc + 1) as u16,
-                AddressingMode::ZeroPageX => (self.memory.read(self.pc + 1) + self.x) as u16,
-                AddressingMode::ZeroPageY => (self.memory.read(self.pc + 1) + self.y) as u16,
-                AddressingMode::Relative => {
-                    let offset:i8 = self.memory.read(self.pc + 1) as i8;
-                    if offset < 0 {
-                        (self.pc + 2).wrapping_add((offset as i16) as u16)
-                    } else {
-                        (self.pc + 2).wrapping_add(offset as u16)
-                    }
-                },
-
-
-                AddressingMode::Absolute => self.memory.read16(self.pc+1),
-
-                AddressingMode::AbsoluteX => {
-                    let addr = self.memory.read16(self.pc+1) + self.x as u16;
-                    page_crossed = pages_differ(addr - self.x as u16, addr);
-                    addr
-                },
-
-                AddressingMode::AbsoluteY => {
-                    let addr = self.memory.read16(self.pc+1) + self.y as u16;
-                    page_crossed = pages_differ(addr - self.y as u16, addr);
-                    addr
-                },
-
-                AddressingMode::Indirect => {
-                    self.memory.read16(self.memory.read16(self.pc+1))
-                },
-
-                AddressingMode::IndexedIndirect => {
-                    self.memory.read16(self.memory.read(self.pc+1) as u16 + self.x as u16)
-                },
-
-                AddressingMode::IndirectIndexed => {
-                    let addr = self.memory.read16(self.memory.read(self.pc+1) as u16) + self.y as u16;
-
-                    page_crossed = pages_differ(addr - self.y as u16, addr);
-                    addr
-                },
-
-            };
+            let address = self.fetch_address(instruction.addr_mode, writes_memory(opcode));
 
             let fun = match opcode {
                 0x00 => CPU::brk,
@@ -441,31 +657,134 @@ impl CPU {
                 _ => panic!("Byte holding larger than 0xff"),
             };
 
-            self.this_cycles = instruction.cycles + if page_crossed { instruction.page_delay } else { 0 };
             (fun, address, instruction.addr_mode, instruction.size, instruction.str_name)
         };
 
-        //println!("{:x}\t{:x}\t{}\tA: {:x}\tX: {:x}\tY: {:x}\tP: {}\tSP: {:x}", self.pc, address, str_name, self.a, self.x, self.y, self.flags_str(), self.sp);
         fun(self, address, addr_mode);
-        self.pc += size as u16;
+        self.pc = self.pc.wrapping_add(size as u16);
 
         self.this_cycles
     }
 
+    // Reads the operand of the instruction at pc and returns its effective address, making the
+    // same bus accesses as the real CPU including the dummy reads
+    fn fetch_address(&mut self, mode: AddressingMode, writes: bool) -> u16 {
+        let pc = self.pc;
+
+        match mode {
+            // Single byte instructions still read the byte after the opcode
+            AddressingMode::Implicit | AddressingMode::Accumulator => {
+                self.read(pc.wrapping_add(1));
+                0
+            },
+
+            // Read by the instruction itself
+            AddressingMode::Immediate => pc.wrapping_add(1),
+
+            AddressingMode::ZeroPage => self.read(pc.wrapping_add(1)) as u16,
+
+            // Indexing never leaves the zero page. The unindexed address is read while adding.
+            AddressingMode::ZeroPageX => {
+                let base = self.read(pc.wrapping_add(1));
+                self.read(base as u16);
+                base.wrapping_add(self.x) as u16
+            },
+
+            AddressingMode::ZeroPageY => {
+                let base = self.read(pc.wrapping_add(1));
+                self.read(base as u16);
+                base.wrapping_add(self.y) as u16
+            },
+
+            AddressingMode::Relative => {
+                let offset = self.read(pc.wrapping_add(1)) as i8;
+                pc.wrapping_add(2).wrapping_add(offset as u16)
+            },
+
+            AddressingMode::Absolute => self.read16(pc.wrapping_add(1)),
+
+            AddressingMode::AbsoluteX => {
+                let base = self.read16(pc.wrapping_add(1));
+                let index = self.x;
+                self.index_address(base, index, writes)
+            },
+
+            AddressingMode::AbsoluteY => {
+                let base = self.read16(pc.wrapping_add(1));
+                let index = self.y;
+                self.index_address(base, index, writes)
+            },
+
+            AddressingMode::Indirect => {
+                // The high byte is fetched without carrying into the page, so JMP ($02FF)
+                // reads $02FF and $0200
+                let pointer = self.read16(pc.wrapping_add(1));
+                let lo = self.read(pointer) as u16;
+                let hi = self.read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF)) as u16;
+                (hi << 8) | lo
+            },
+
+            AddressingMode::IndexedIndirect => {
+                let pointer = self.read(pc.wrapping_add(1));
+                self.read(pointer as u16);
+                self.read16_zero_page(pointer.wrapping_add(self.x))
+            },
+
+            AddressingMode::IndirectIndexed => {
+                let pointer = self.read(pc.wrapping_add(1));
+                let base = self.read16_zero_page(pointer);
+                let index = self.y;
+                self.index_address(base, index, writes)
+            },
+        }
+    }
+
+    // The CPU reads from the indexed address before carrying into the high byte. Reads that stay
+    // on the same page are done at that point, anything else has to fix the address and go again.
+    fn index_address(&mut self, base: u16, index: u8, writes: bool) -> u16 {
+        let address = base.wrapping_add(index as u16);
+
+        if writes || pages_differ(base, address) {
+            self.read((base & 0xFF00) | (address & 0x00FF));
+        }
+
+        address
+    }
+
+    // Pointers stored in the zero page wrap around from $FF to $00 for their high byte
+    fn read16_zero_page(&mut self, pointer: u8) -> u16 {
+        let lo = self.read(pointer as u16) as u16;
+        let hi = self.read(pointer.wrapping_add(1) as u16) as u16;
+
+        (hi << 8) | lo
+    }
+
     fn nmi(&mut self) {
         let pc = self.pc;
 
-        self.push16(pc);
-        self.php(0, AddressingMode::Implicit);
-        self.pc = self.memory.read16(0xFFFA);
-        self.i = 1;
-        self.this_cycles += 7;
+        // Two cycles are spent fetching the instruction that gets replaced
+        self.read(pc);
+        self.read(pc);
+        self.interrupt(pc, 0xFFFA, false);
 
         //println!("NMI Occured");
     }
 
-    fn flags(&self) -> u8 {
-        let mut flags:u8 = 0;;
+    // Shared by NMI, IRQ and BRK. Pushes the return address and status then jumps through the vector.
+    fn interrupt(&mut self, return_address: u16, vector: u16, brk: bool) {
+        self.push16(return_address);
+
+        // Bit 5 is always set on the stack, the B flag only when the push came from BRK or PHP
+        let flags = (self.flags() & 0b11001111) | 0b00100000 | if brk { 0b00010000 } else { 0 };
+        self.push(flags);
+
+        self.i = 1;
+        self.pc = self.read16(vector);
+    }
+
+    // The status register packed into a byte, NV-BDIZC
+    pub fn flags(&self) -> u8 {
+        let mut flags:u8 = 0;
         flags |= self.c << 0;
         flags |= self.z << 1;
         flags |= self.i << 2;
@@ -478,7 +797,7 @@ impl CPU {
         flags
     }
 
-    fn set_flags(&mut self, flags:u8) {
+    pub fn set_flags(&mut self, flags:u8) {
         self.c = (flags & 1 << 0) >> 0;
         self.z = (flags & 1 << 1) >> 1;
         self.i = (flags & 1 << 2) >> 2;
@@ -489,12 +808,92 @@ impl CPU {
         self.n = (flags & 1 << 7) >> 7;
     }
 
-    fn irq(&mut self) {
+    // Returns whether the interrupt was taken
+    fn irq(&mut self) -> bool {
+        // Maskable, so ignored while the interrupt disable flag is set
+        if self.i == 1 {
+            return false;
+        }
+
+        let pc = self.pc;
 
+        self.read(pc);
+        self.read(pc);
+        self.interrupt(pc, 0xFFFE, false);
+        true
     }
 
     fn adc(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
+        let mem = self.read(address);
+        self.add_with_mode(mem);
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.d == 1 && self.variant == Variant::Nmos6502
+    }
+
+    fn add_with_mode(&mut self, mem: u8) {
+        if self.decimal_mode() {
+            self.add_decimal(mem);
+        } else {
+            self.add(mem);
+        }
+    }
+
+    fn subtract_with_mode(&mut self, mem: u8) {
+        if self.decimal_mode() {
+            self.subtract_decimal(mem);
+        } else {
+            // Subtracting is adding the one's complement, carry being the inverse of borrow
+            self.add(!mem);
+        }
+    }
+
+    // BCD addition the way the NMOS 6502 does it. Z comes from the binary sum and N and V from
+    // the sum before the high digit is adjusted, which only means anything for valid BCD input.
+    fn add_decimal(&mut self, mem: u8) {
+        let binary = self.a.wrapping_add(mem).wrapping_add(self.c);
+
+        let mut lo = (self.a & 0x0F) as u16 + (mem & 0x0F) as u16 + self.c as u16;
+        if lo >= 0x0A {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
+        }
+
+        let mut res = (self.a & 0xF0) as u16 + (mem & 0xF0) as u16 + lo;
+
+        self.z = if binary == 0 { 1 } else { 0 };
+        self.n = if res & 0x80 != 0 { 1 } else { 0 };
+        self.v = if (self.a ^ res as u8) & 0x80 != 0 && (mem ^ self.a) & 0x80 == 0 { 1 } else { 0 };
+
+        if res >= 0xA0 {
+            res += 0x60;
+        }
+
+        self.c = if res >= 0x100 { 1 } else { 0 };
+        self.a = res as u8;
+    }
+
+    // BCD subtraction, all the flags are set the same as a binary SBC
+    fn subtract_decimal(&mut self, mem: u8) {
+        let a = self.a;
+        let borrow = 1 - self.c as i16;
+
+        let mut lo = (a & 0x0F) as i16 - (mem & 0x0F) as i16 - borrow;
+        if lo < 0 {
+            lo = ((lo - 0x06) & 0x0F) - 0x10;
+        }
+
+        let mut res = (a & 0xF0) as i16 - (mem & 0xF0) as i16 + lo;
+        if res < 0 {
+            res -= 0x60;
+        }
+
+        self.add(!mem);
+        self.a = res as u8;
+    }
+
+    // Adds to the accumulator with carry, setting C, V, Z and N
+    fn add(&mut self, mem: u8) {
         let (res1, overflow1) = mem.overflowing_add(self.a);
         let (res2, overflow2) = res1.overflowing_add(self.c);
 
@@ -506,115 +905,152 @@ impl CPU {
         self.a = res2;
     }
 
-    fn ahx(&mut self, address: u16, mode: AddressingMode){}
-    fn alr(&mut self, address: u16, mode: AddressingMode){}
-    fn anc(&mut self, address: u16, mode: AddressingMode){}
+    fn ahx(&mut self, address: u16, mode: AddressingMode) {
+        let value = self.a & self.x;
+        self.store_high_byte_and(value, address, mode);
+    }
+
+    fn alr(&mut self, address: u16, mode: AddressingMode) {
+        let value = self.a & self.read(address);
+
+        self.c = value & 1;
+        self.a = value >> 1;
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = 0;
+    }
+
+    fn anc(&mut self, address: u16, mode: AddressingMode) {
+        self.and(address, mode);
+        self.c = self.n;
+    }
 
     fn and(&mut self, address: u16, mode: AddressingMode) {
-        self.a = self.a & self.memory.read(address);
+        self.a = self.a & self.read(address);
         self.z = if self.a == 0 { 1 } else { 0 };
         self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 
-    fn arr(&mut self, address: u16, mode: AddressingMode){}
+    fn arr(&mut self, address: u16, mode: AddressingMode) {
+        self.a = ((self.a & self.read(address)) >> 1) | (self.c << 7);
+
+        // Carry and overflow come out of the adder rather than the shift
+        self.c = (self.a >> 6) & 1;
+        self.v = ((self.a >> 6) ^ (self.a >> 5)) & 1;
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+    }
 
     fn asl(&mut self, address: u16, mode: AddressingMode) {
         match mode {
             AddressingMode::Accumulator => {
-                let res = self.a << 1;
-                let overflow = self.a & 0b10000000 != 0;
-
-                self.a = res;
-
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
-                self.c = if overflow { 1 } else { 0 };
+                let a = self.a;
+                self.a = self.shift_left(a);
             }
             _ => {
-                let mem = self.memory.read(address);
-                let res = mem << 1;
-                let overflow = mem & 0b10000000 != 0;
-
-                self.z = if res == 0 { 1 } else { 0 };
-                self.n = if res & 0b10000000 != 0 { 1 } else { 0 };
-                self.c = if overflow { 1 } else { 0 };
-
-                self.memory.write(res as u8, address);
+                self.modify(address, CPU::shift_left);
             }
         }
     }
 
-    fn axs(&mut self, address: u16, mode: AddressingMode){}
+    fn shift_left(&mut self, value: u8) -> u8 {
+        let res = value << 1;
+
+        self.c = value >> 7;
+        self.z = if res == 0 { 1 } else { 0 };
+        self.n = if res & 0b10000000 != 0 { 1 } else { 0 };
+
+        res
+    }
+
+    fn axs(&mut self, address: u16, mode: AddressingMode) {
+        let mem = self.read(address);
+        let value = self.a & self.x;
+
+        self.x = value.wrapping_sub(mem);
+        self.c = if value >= mem { 1 } else { 0 };
+        self.z = if self.x == 0 { 1 } else { 0 };
+        self.n = if self.x & 0b10000000 != 0 { 1 } else { 0 };
+    }
 
     /* All the branch instruction */
-    /* FIXME: address subtraction by 2 is not safe and should be fixed at some point although it
-     * probably won't cause issues because who would ever be executing in ram */
+    // A taken branch costs one extra cycle, or two if it lands on a different page
+    fn branch(&mut self, address: u16) {
+        // The next opcode is fetched before the CPU notices it has to branch, and the target is
+        // fetched without the carry into the high byte first when the branch crosses a page
+        let next = self.pc.wrapping_add(2);
+        self.read(next);
+        if pages_differ(next, address) {
+            self.read((next & 0xFF00) | (address & 0x00FF));
+        }
+
+        // step() adds the size of the branch after we return
+        self.pc = address.wrapping_sub(2);
+    }
+
     fn bcc(&mut self, address: u16, mode: AddressingMode) {
         if self.c == 0 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn bcs(&mut self, address: u16, mode: AddressingMode) {
         if self.c == 1 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn beq(&mut self, address: u16, mode: AddressingMode) {
         if self.z == 1{
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn bit(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
+        let mem = self.read(address);
         let res = mem & self.a;
 
         self.z = if res == 0 { 1 } else { 0 };
-        self.v = if res & 0b1000000 != 0 { 1 } else { 0 };
-        self.n = if res & 0b10000000 != 0 { 1 } else { 0 };
+        self.v = if mem & 0b1000000 != 0 { 1 } else { 0 };
+        self.n = if mem & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn bmi(&mut self, address: u16, mode: AddressingMode) {
         if self.n == 1{
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn bne(&mut self, address: u16, mode: AddressingMode) {
         if self.z == 0 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn bpl(&mut self, address: u16, mode: AddressingMode) {
         if self.n == 0 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn brk(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        // BRK is followed by a padding byte that the return address skips over
+        let pc = self.pc.wrapping_add(2);
+
+        self.interrupt(pc, 0xFFFE, true);
+
+        // step() adds the size of BRK after we return
+        self.pc = self.pc.wrapping_sub(1);
     }
 
     fn bvc(&mut self, address: u16, mode: AddressingMode) {
         if self.v == 0 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
     fn bvs(&mut self, address: u16, mode: AddressingMode) {
         if self.v == 1 {
-            self.pc = address - 2;
-            self.this_cycles += if pages_differ(self.pc, address) { 1 } else { 0 };
+            self.branch(address);
         }
     }
 
@@ -635,40 +1071,40 @@ impl CPU {
     }
 
     fn cmp(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
+        let mem = self.read(address);
+        let register = self.a;
+        self.compare(register, mem);
+    }
 
-        self.c = if self.a >= mem { 1 } else { 0 };
-        self.z = if self.a == mem { 1 } else { 0 };
-        self.v = if self.a < mem { 1 } else { 0 };
+    fn compare(&mut self, register: u8, mem: u8) {
+        self.c = if register >= mem { 1 } else { 0 };
+        self.z = if register == mem { 1 } else { 0 };
+        self.n = if register.wrapping_sub(mem) & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn cpx(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
-
-        self.c = if self.x >= mem { 1 } else { 0 };
-        self.z = if self.x == mem { 1 } else { 0 };
-        self.v = if self.x < mem { 1 } else { 0 };
+        let mem = self.read(address);
+        let register = self.x;
+        self.compare(register, mem);
     }
 
     fn cpy(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
-
-        self.c = if self.y >= mem { 1 } else { 0 };
-        self.z = if self.y == mem { 1 } else { 0 };
-        self.v = if self.y < mem { 1 } else { 0 };
+        let mem = self.read(address);
+        let register = self.y;
+        self.compare(register, mem);
     }
 
     fn dcp(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, |_, value| value.wrapping_sub(1));
+        let a = self.a;
+        self.compare(a, mem);
     }
 
     fn dec(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address).wrapping_sub(1);
+        let mem = self.modify(address, |_, value| value.wrapping_sub(1));
 
         self.z = if mem == 0 { 1 } else { 0 };
         self.n = if mem & 0b10000000 != 0 { 1 } else { 0 };
-
-        self.memory.write(mem, address);
     }
 
     fn dex(&mut self, address: u16, mode: AddressingMode) {
@@ -684,21 +1120,19 @@ impl CPU {
     }
 
     fn eor(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address);
+        let mem = self.read(address);
 
         self.a = self.a ^ mem;
 
-        self.z = if self.y == 0 { 1 } else { 0 };
-        self.n = if self.y & 0b10000000 != 0 { 1 } else { 0 };
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn inc(&mut self, address: u16, mode: AddressingMode) {
-        let mem = self.memory.read(address).wrapping_add(1);
+        let mem = self.modify(address, |_, value| value.wrapping_add(1));
 
         self.z = if mem == 0 { 1 } else { 0 };
         self.n = if mem & 0b10000000 != 0 { 1 } else { 0 };
-
-        self.memory.write(mem, address);
     }
 
     fn inx(&mut self, address: u16, mode: AddressingMode) {
@@ -714,46 +1148,64 @@ impl CPU {
     }
 
     fn isc(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, |_, value| value.wrapping_add(1));
+        self.subtract_with_mode(mem);
     }
 
     fn jmp(&mut self, address: u16, mode: AddressingMode) {
-        self.pc = address - 3;
+        self.pc = address.wrapping_sub(3);
     }
     fn jsr(&mut self, address: u16, mode: AddressingMode) {
-        let pcval = self.pc + 3 - 1;
+        let pcval = self.pc.wrapping_add(2);
 
+        self.dummy_stack_read();
         self.push16(pcval);
 
-        self.pc = address - 3;
+        self.pc = address.wrapping_sub(3);
     }
 
-    fn kil(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
-    }
+    // Jams the CPU. KIL has a size of 0 so the same opcode is executed forever.
+    fn kil(&mut self, address: u16, mode: AddressingMode) {}
+
     fn las(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let value = self.read(address) & self.sp;
+
+        self.a = value;
+        self.x = value;
+        self.sp = value;
+        self.z = if value == 0 { 1 } else { 0 };
+        self.n = if value & 0b10000000 != 0 { 1 } else { 0 };
     }
+
     fn lax(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let value = match mode {
+            // Unstable, the 0xEE "magic" constant is what most NES CPUs settle on
+            AddressingMode::Immediate => (self.a | 0xEE) & self.read(address),
+            _ => self.read(address),
+        };
+
+        self.a = value;
+        self.x = value;
+        self.z = if value == 0 { 1 } else { 0 };
+        self.n = if value & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn lda(&mut self, address: u16, mode: AddressingMode) {
-        self.a = self.memory.read(address);
+        self.a = self.read(address);
 
         self.z = if self.a == 0 { 1 } else { 0 };
         self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn ldx(&mut self, address: u16, mode: AddressingMode) {
-        self.x = self.memory.read(address);
+        self.x = self.read(address);
 
         self.z = if self.x == 0 { 1 } else { 0 };
         self.n = if self.x & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn ldy(&mut self, address: u16, mode: AddressingMode) {
-        self.y = self.memory.read(address);
+        self.y = self.read(address);
 
         self.z = if self.y == 0 { 1 } else { 0 };
         self.n = if self.y & 0b10000000 != 0 { 1 } else { 0 };
@@ -762,34 +1214,50 @@ impl CPU {
     fn lsr(&mut self, address: u16, mode: AddressingMode) {
         match mode {
             AddressingMode::Accumulator => {
-                let res = self.a >> 1;
-                let overflow = self.a & 1 != 0;
-
-                self.a = res;
-
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+                let a = self.a;
+                self.a = self.shift_right(a);
             }
 
             _ => {
-                let mem = self.memory.read(address);
-                let res = mem >> 1;
-                let overflow = mem & 1 != 0;
-
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if mem == 0 { 1 } else { 0 };
-                self.n = if mem & 0b10000000 != 0 { 1 } else { 0 };
-
-                self.memory.write(res, address);
+                self.modify(address, CPU::shift_right);
             }
         }
     }
 
-    fn nop(&mut self, address: u16, mode: AddressingMode) {}
+    fn shift_right(&mut self, value: u8) -> u8 {
+        let res = value >> 1;
+
+        self.c = value & 1;
+        self.z = if res == 0 { 1 } else { 0 };
+        self.n = 0;
+
+        res
+    }
+
+    // Read-modify-write instructions write the unmodified value back while they work out the
+    // result, then write the result
+    fn modify<F>(&mut self, address: u16, f: F) -> u8 where F: FnOnce(&mut CPU<M>, u8) -> u8 {
+        let mem = self.read(address);
+        self.write(mem, address);
+
+        let res = f(self, mem);
+        self.write(res, address);
+
+        res
+    }
+
+    fn nop(&mut self, address: u16, mode: AddressingMode) {
+        // The unofficial NOPs with an operand still read it
+        match mode {
+            AddressingMode::Implicit => {},
+            _ => {
+                self.read(address);
+            },
+        }
+    }
 
     fn ora(&mut self, address: u16, mode: AddressingMode) {
-        self.a = self.a | self.memory.read(address);
+        self.a = self.a | self.read(address);
         self.z = if self.a == 0 { 1 } else { 0 };
         self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
@@ -800,11 +1268,13 @@ impl CPU {
     }
 
     fn php(&mut self, address: u16, mode: AddressingMode) {
-        let flags = self.flags();
+        // PHP pushes with both the B flag and bit 5 set
+        let flags = self.flags() | 0b00110000;
         self.push(flags);
     }
 
     fn pla(&mut self, address: u16, mode: AddressingMode) {
+        self.dummy_stack_read();
         self.a = self.pop();
 
         self.z = if self.a == 0 { 1 } else { 0 };
@@ -812,96 +1282,103 @@ impl CPU {
     }
 
     fn plp(&mut self, address: u16, mode: AddressingMode) {
+        self.dummy_stack_read();
         let flags = self.pop();
         self.set_flags(flags);
+
+        // B and bit 5 only exist on the stack copy of the flags
+        self.b = 0;
+        self.u = 1;
     }
 
 
     fn rla(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, CPU::rotate_left);
+
+        self.a = self.a & mem;
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn rol(&mut self, address: u16, mode: AddressingMode) {
         match mode {
             AddressingMode::Accumulator => {
-                let (mut newval, overflow) = self.a.overflowing_shl(1);
-                newval |= self.c;
-                self.a = newval;
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+                let a = self.a;
+                self.a = self.rotate_left(a);
             }
 
             _ => {
-                let (mut newval, overflow) = {
-                    self.memory.read(address).overflowing_shl(1)
-                };
-
-                newval |= self.c;
-                self.memory.write(newval, address);
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+                self.modify(address, CPU::rotate_left);
             }
         }
     }
 
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let res = (value << 1) | self.c;
+
+        self.c = value >> 7;
+        self.z = if res == 0 { 1 } else { 0 };
+        self.n = if res & 0b10000000 != 0 { 1 } else { 0 };
+
+        res
+    }
+
     fn ror(&mut self, address: u16, mode: AddressingMode) {
         match mode {
             AddressingMode::Accumulator => {
-                let (mut newval, overflow) = self.a.overflowing_shr(1);
-                newval |= self.c << 7;
-                self.a = newval;
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+                let a = self.a;
+                self.a = self.rotate_right(a);
             }
 
             _ => {
-                let (mut newval, overflow) = {
-                    self.memory.read(address).overflowing_shr(1)
-                };
-
-                newval |= self.c << 7;
-                self.memory.write(newval, address);
-                self.c = if overflow { 1 } else { 0 };
-                self.z = if self.a == 0 { 1 } else { 0 };
-                self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
+                self.modify(address, CPU::rotate_right);
             }
         }
     }
 
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let res = (value >> 1) | (self.c << 7);
+
+        self.c = value & 1;
+        self.z = if res == 0 { 1 } else { 0 };
+        self.n = if res & 0b10000000 != 0 { 1 } else { 0 };
+
+        res
+    }
+
     fn rra(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, CPU::rotate_right);
+        self.add_with_mode(mem);
     }
 
     fn rti(&mut self, address: u16, mode: AddressingMode) {
+        self.dummy_stack_read();
         let flags = self.pop();
         let pc = self.pop16();
 
         self.set_flags(flags);
+        self.b = 0;
+        self.u = 1;
         //Fixme SUCH A HACK OMG PLS
-        self.pc = pc-1;
+        self.pc = pc.wrapping_sub(1);
     }
 
     fn rts(&mut self, address: u16, mode: AddressingMode) {
+        self.dummy_stack_read();
         self.pc = self.pop16();
+
+        // The pulled address points at the last byte of the JSR, one more cycle moves past it
+        let pc = self.pc;
+        self.read(pc);
     }
 
     fn sax(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        self.write(self.a & self.x, address);
     }
 
     fn sbc(&mut self, address: u16, mode: AddressingMode) {
-        let olda = self.a;
-        let mem = self.memory.read(address);
-        self.a = self.a.wrapping_sub(mem).wrapping_sub(1 - self.c);
-        let res16:u16 = (olda as u16).wrapping_sub(mem as u16).wrapping_sub((1 - self.c) as u16);
-
-        self.z = if self.a == 0 { 1 } else { 0 };
-        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
-        self.c = if res16 > 0xFF { 1 } else { 0 };
-        self.v = if (olda ^ self.a) & 0b10000000 != 0 && (mem ^ olda) & 0b10000000 == 0 { 1 } else { 0 };
+        let mem = self.read(address);
+        self.subtract_with_mode(mem);
     }
 
     fn sec(&mut self, address: u16, mode: AddressingMode) {
@@ -917,32 +1394,66 @@ impl CPU {
     }
 
     fn shx(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let value = self.x;
+        self.store_high_byte_and(value, address, mode);
     }
+
     fn shy(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let value = self.y;
+        self.store_high_byte_and(value, address, mode);
+    }
+
+    // SHX, SHY, AHX and TAS store the value ANDed with the high byte of the base address plus one.
+    // When indexing crosses a page the result also replaces the high byte of the address.
+    fn store_high_byte_and(&mut self, value: u8, address: u16, mode: AddressingMode) {
+        let index = match mode {
+            AddressingMode::AbsoluteX => self.x,
+            _ => self.y,
+        };
+        let base = address.wrapping_sub(index as u16);
+        let res = value & ((base >> 8) as u8).wrapping_add(1);
+
+        let address = if pages_differ(base, address) {
+            ((res as u16) << 8) | (address & 0xFF)
+        } else {
+            address
+        };
+
+        self.write(res, address);
     }
+
     fn slo(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, CPU::shift_left);
+
+        self.a = self.a | mem;
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
+
     fn sre(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        let mem = self.modify(address, CPU::shift_right);
+
+        self.a = self.a ^ mem;
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn sta(&mut self, address: u16, mode: AddressingMode) {
-        self.memory.write(self.a, address);
+        self.write(self.a, address);
     }
 
     fn stx(&mut self, address: u16, mode: AddressingMode) {
-        self.memory.write(self.x, address);
+        self.write(self.x, address);
     }
 
     fn sty(&mut self, address: u16, mode: AddressingMode) {
-        self.memory.write(self.y, address);
+        self.write(self.y, address);
     }
 
     fn tas(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        self.sp = self.a & self.x;
+        let value = self.sp;
+        self.store_high_byte_and(value, address, mode);
     }
 
     fn tax(&mut self, address: u16, mode: AddressingMode) {
@@ -959,6 +1470,8 @@ impl CPU {
 
     fn tsx(&mut self, address: u16, mode: AddressingMode) {
         self.x = self.sp;
+        self.z = if self.x == 0 { 1 } else { 0 };
+        self.n = if self.x & 0b10000000 != 0 { 1 } else { 0 };
     }
 
     fn txa(&mut self, address: u16, mode: AddressingMode) {
@@ -978,13 +1491,16 @@ impl CPU {
     }
 
     fn xaa(&mut self, address: u16, mode: AddressingMode) {
-        panic!("Not implemented!");
+        // Unstable, uses the same 0xEE constant as LAX immediate
+        self.a = (self.a | 0xEE) & self.x & self.read(address);
+        self.z = if self.a == 0 { 1 } else { 0 };
+        self.n = if self.a & 0b10000000 != 0 { 1 } else { 0 };
     }
 }
 
 // The possible addressing modes of an instruction
 // TODO: Put important details in comments above each
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AddressingMode {
     Implicit,
     Accumulator,
@@ -1004,7 +1520,7 @@ pub enum AddressingMode {
 // Format of instructions
 pub struct Instruction<'a> {
     // The name of the instruction for informational purposes
-    str_name: &'a str,
+    pub str_name: &'a str,
 
     // The cycle delay encountered when crossing page boundary
     page_delay: u8,
@@ -1013,268 +1529,268 @@ pub struct Instruction<'a> {
     cycles: u8,
 
     // Addressing mode used in memory lookup
-    addr_mode: AddressingMode,
+    pub addr_mode: AddressingMode,
 
     // Actual size of the instruction besides the 1 byte opcode
-    size: u32,
+    pub size: u32,
 }
 
 // All possible instructions and their properties. This makes decoding as simple as an array lookup
-const INSTRUCTIONS: [Instruction; 256] = [
+pub const INSTRUCTIONS: [Instruction; 256] = [
     Instruction{str_name: "BRK", cycles: 7, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ORA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SLO", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "SLO", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "NOP", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "ORA", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "ASL", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "SLO", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "SLO", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "PHP", cycles: 3, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ORA", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "ASL", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Accumulator},
-    Instruction{str_name: "ANC", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "ANC", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "ORA", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "ASL", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "SLO", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "SLO", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BPL", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "ORA", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SLO", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "SLO", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "ORA", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "ASL", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "SLO", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "SLO", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "CLC", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ORA", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SLO", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "SLO", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "ORA", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "ASL", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "SLO", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "SLO", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "JSR", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "AND", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RLA", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "RLA", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "BIT", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "AND", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "ROL", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "RLA", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "RLA", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "PLP", cycles: 4, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "AND", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "ROL", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Accumulator},
-    Instruction{str_name: "ANC", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "ANC", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "BIT", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "AND", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "ROL", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "RLA", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "RLA", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BMI", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "AND", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RLA", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "RLA", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "AND", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "ROL", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "RLA", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "RLA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "SEC", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "AND", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RLA", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "RLA", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "AND", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "ROL", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "RLA", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "RLA", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "RTI", cycles: 6, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "EOR", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SRE", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "SRE", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "NOP", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "EOR", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "LSR", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "SRE", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "SRE", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "PHA", cycles: 3, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "EOR", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "LSR", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Accumulator},
-    Instruction{str_name: "ALR", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "ALR", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "JMP", cycles: 3, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "EOR", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "LSR", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "SRE", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "SRE", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BVC", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "EOR", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SRE", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "SRE", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "EOR", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "LSR", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "SRE", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "SRE", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "CLI", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "EOR", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SRE", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "SRE", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "EOR", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "LSR", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "SRE", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "SRE", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "RTS", cycles: 6, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ADC", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RRA", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "RRA", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "NOP", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "ADC", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "ROR", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "RRA", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "RRA", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "PLA", cycles: 4, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ADC", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "ROR", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Accumulator},
-    Instruction{str_name: "ARR", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "ARR", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "JMP", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::Indirect},
     Instruction{str_name: "ADC", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "ROR", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "RRA", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "RRA", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BVS", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "ADC", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RRA", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "RRA", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "ADC", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "ROR", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "RRA", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "RRA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "SEI", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "ADC", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "RRA", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "RRA", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "ADC", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "ROR", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "RRA", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "RRA", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "STA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
-    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
-    Instruction{str_name: "SAX", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "SAX", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "STY", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "STA", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "STX", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "SAX", cycles: 3, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "SAX", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "DEY", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "TXA", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "XAA", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "XAA", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "STY", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "STA", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "STX", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "SAX", cycles: 4, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "SAX", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BCC", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "STA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "AHX", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "AHX", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "STY", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "STA", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "STX", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageY},
-    Instruction{str_name: "SAX", cycles: 4, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageY},
+    Instruction{str_name: "SAX", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageY},
     Instruction{str_name: "TYA", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "STA", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "TXS", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "TAS", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
-    Instruction{str_name: "SHY", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "TAS", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "SHY", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "STA", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "SHX", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
-    Instruction{str_name: "AHX", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "SHX", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "AHX", cycles: 5, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "LDY", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "LDA", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "LDX", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
-    Instruction{str_name: "LAX", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "LAX", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "LDY", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "LDA", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "LDX", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "LAX", cycles: 3, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "LAX", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "TAY", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "LDA", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "TAX", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "LAX", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "LAX", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "LDY", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "LDA", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "LDX", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "LAX", cycles: 4, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "LAX", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BCS", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "LDA", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "LAX", cycles: 5, page_delay: 1, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "LAX", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "LDY", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "LDA", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "LDX", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageY},
-    Instruction{str_name: "LAX", cycles: 4, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageY},
+    Instruction{str_name: "LAX", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageY},
     Instruction{str_name: "CLV", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "LDA", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "TSX", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "LAS", cycles: 4, page_delay: 1, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "LAS", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "LDY", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "LDA", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "LDX", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
-    Instruction{str_name: "LAX", cycles: 4, page_delay: 1, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "LAX", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "CPY", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "CMP", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
-    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
-    Instruction{str_name: "DCP", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "DCP", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "CPY", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "CMP", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "DEC", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "DCP", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "DCP", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "INY", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "CMP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "DEX", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "AXS", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "AXS", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "CPY", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "CMP", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "DEC", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "DCP", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "DCP", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BNE", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "CMP", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "DCP", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "DCP", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "CMP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "DEC", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "DCP", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "DCP", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "CLD", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "CMP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "DCP", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "DCP", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "CMP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "DEC", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "DCP", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "DCP", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "CPX", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "SBC", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
-    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
-    Instruction{str_name: "ISC", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndexedIndirect},
+    Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "ISC", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndexedIndirect},
     Instruction{str_name: "CPX", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "SBC", cycles: 3, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "INC", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
-    Instruction{str_name: "ISC", cycles: 5, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPage},
+    Instruction{str_name: "ISC", cycles: 5, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPage},
     Instruction{str_name: "INX", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "SBC", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "SBC", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Immediate},
+    Instruction{str_name: "SBC", cycles: 2, page_delay: 0, size: 2, addr_mode: AddressingMode::Immediate},
     Instruction{str_name: "CPX", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "SBC", cycles: 4, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "INC", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
-    Instruction{str_name: "ISC", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::Absolute},
+    Instruction{str_name: "ISC", cycles: 6, page_delay: 0, size: 3, addr_mode: AddressingMode::Absolute},
     Instruction{str_name: "BEQ", cycles: 2, page_delay: 1, size: 2, addr_mode: AddressingMode::Relative},
     Instruction{str_name: "SBC", cycles: 5, page_delay: 1, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "KIL", cycles: 2, page_delay: 0, size: 0, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "ISC", cycles: 8, page_delay: 0, size: 0, addr_mode: AddressingMode::IndirectIndexed},
+    Instruction{str_name: "ISC", cycles: 8, page_delay: 0, size: 2, addr_mode: AddressingMode::IndirectIndexed},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "SBC", cycles: 4, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "INC", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
-    Instruction{str_name: "ISC", cycles: 6, page_delay: 0, size: 0, addr_mode: AddressingMode::ZeroPageX},
+    Instruction{str_name: "ISC", cycles: 6, page_delay: 0, size: 2, addr_mode: AddressingMode::ZeroPageX},
     Instruction{str_name: "SED", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
     Instruction{str_name: "SBC", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 2, page_delay: 0, size: 1, addr_mode: AddressingMode::Implicit},
-    Instruction{str_name: "ISC", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteY},
+    Instruction{str_name: "ISC", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteY},
     Instruction{str_name: "NOP", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "SBC", cycles: 4, page_delay: 1, size: 3, addr_mode: AddressingMode::AbsoluteX},
     Instruction{str_name: "INC", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
-    Instruction{str_name: "ISC", cycles: 7, page_delay: 0, size: 0, addr_mode: AddressingMode::AbsoluteX},
+    Instruction{str_name: "ISC", cycles: 7, page_delay: 0, size: 3, addr_mode: AddressingMode::AbsoluteX},
     ];
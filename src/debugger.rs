@@ -0,0 +1,246 @@
+pub mod repl;
+pub mod gdb;
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+use bus::Bus;
+use cpu::CPU;
+
+// Which memory map an address is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Space {
+    Cpu,
+    Ppu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Stops execution when any address from start to end (inclusive) is accessed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub space: Space,
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, space: Space, access: Access, address: u16) -> bool {
+        let wanted = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+
+        wanted && space == self.space && address >= self.start && address <= self.end
+    }
+}
+
+// An access that hit a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub space: Space,
+    pub access: Access,
+    pub address: u16,
+    pub data: u8,
+}
+
+// The watchpoints, shared with the memory maps which note down every access that hits one
+pub struct Watches {
+    points: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+}
+
+impl Watches {
+    pub fn new() -> Watches {
+        Watches { points: Vec::new(), hits: Vec::new() }
+    }
+
+    pub fn check(&mut self, space: Space, access: Access, address: u16, data: u8) {
+        if self.points.iter().any(|point| point.matches(space, access, address)) {
+            self.hits.push(WatchHit { space: space, access: access, address: address, data: data });
+        }
+    }
+}
+
+impl Default for Watches {
+    fn default() -> Watches {
+        Watches::new()
+    }
+}
+
+// Why the debugger stopped before an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    // Asked to with pause()
+    Paused,
+    Breakpoint,
+    // The first watchpoint hit by the last instruction
+    Watchpoint(WatchHit),
+    // Finished a step, next or finish
+    Step,
+    Scanline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Running,
+    Paused,
+    // Instructions left to run
+    StepInto(u32),
+    // Running until a JSR returns to pc with the stack back at sp
+    StepOver { pc: u16, sp: u8 },
+    // Running until an RTS or RTI pulls the stack above sp
+    StepOut { sp: u8 },
+    Scanline(u16),
+}
+
+// Decides when to stop the CPU. Call check() before every instruction and hand control to the
+// user, e.g. through a repl::Repl, whenever it says to stop. Execution then carries on until the
+// next breakpoint or watchpoint, or whatever the last call to step_into(), step_over() etc. asked for.
+pub struct Debugger {
+    pub breakpoints: Vec<u16>,
+    watches: Rc<RefCell<Watches>>,
+    mode: Mode,
+
+    // The opcode and scanline from the last check
+    last_opcode: u8,
+    last_scanline: u16,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            watches: Rc::new(RefCell::new(Watches::new())),
+            mode: Mode::Running,
+            last_opcode: 0,
+            last_scanline: 0,
+        }
+    }
+
+    // Lets watchpoints see the CPU's and PPU's memory accesses
+    pub fn attach(&self, cpu: &mut CPU) {
+        cpu.memory.watches = Some(self.watches.clone());
+        cpu.memory.ppu.watch(Some(self.watches.clone()));
+    }
+
+    pub fn detach(&self, cpu: &mut CPU) {
+        cpu.memory.watches = None;
+        cpu.memory.ppu.watch(None);
+    }
+
+    pub fn watchpoints(&self) -> Ref<'_, Vec<Watchpoint>> {
+        Ref::map(self.watches.borrow(), |watches| &watches.points)
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watches.borrow_mut().points.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        let mut watches = self.watches.borrow_mut();
+        if index < watches.points.len() {
+            Some(watches.points.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.mode != Mode::Paused
+    }
+
+    // Stops before the next instruction
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    // Runs until a breakpoint or watchpoint
+    pub fn resume(&mut self) {
+        self.mode = Mode::Running;
+    }
+
+    // Runs count instructions, following JSRs and interrupts
+    pub fn step_into(&mut self, count: u32) {
+        self.mode = Mode::StepInto(count);
+    }
+
+    // Runs one instruction, or a whole subroutine if it's a JSR
+    pub fn step_over(&mut self, cpu: &CPU) {
+        let state = cpu.state();
+        self.mode = if cpu.memory.peek(state.pc) == JSR {
+            Mode::StepOver { pc: state.pc.wrapping_add(3), sp: state.sp }
+        } else {
+            Mode::StepInto(1)
+        };
+    }
+
+    // Runs until the current subroutine or interrupt handler returns
+    pub fn step_out(&mut self, cpu: &CPU) {
+        self.mode = Mode::StepOut { sp: cpu.state().sp };
+    }
+
+    // Runs until the PPU starts on scanline
+    pub fn run_to_scanline(&mut self, scanline: u16) {
+        self.mode = Mode::Scanline(scanline);
+    }
+
+    // Whether to stop before the instruction at pc, and why
+    pub fn check(&mut self, cpu: &CPU) -> Option<Stop> {
+        let state = cpu.state();
+        let (_, scanline, _) = cpu.memory.video_position();
+        let last_opcode = self.last_opcode;
+        let last_scanline = self.last_scanline;
+        self.last_opcode = cpu.memory.peek(state.pc);
+        self.last_scanline = scanline;
+
+        let hit = {
+            let mut watches = self.watches.borrow_mut();
+            let hit = watches.hits.first().cloned();
+            watches.hits.clear();
+            hit
+        };
+
+        let stop = if let Some(hit) = hit {
+            Some(Stop::Watchpoint(hit))
+        } else if self.breakpoints.contains(&state.pc) {
+            Some(Stop::Breakpoint)
+        } else {
+            match self.mode {
+                Mode::Running => None,
+                Mode::Paused => Some(Stop::Paused),
+                Mode::StepInto(count) if count > 1 => {
+                    self.mode = Mode::StepInto(count - 1);
+                    None
+                },
+                Mode::StepInto(_) => Some(Stop::Step),
+                Mode::StepOver { pc, sp } if state.pc == pc && state.sp == sp => Some(Stop::Step),
+                Mode::StepOut { sp } if (last_opcode == RTS || last_opcode == RTI) && state.sp > sp => Some(Stop::Step),
+                Mode::Scanline(line) if scanline == line && last_scanline != line => Some(Stop::Scanline),
+                _ => None,
+            }
+        };
+
+        if stop.is_some() {
+            self.mode = Mode::Paused;
+        }
+
+        stop
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
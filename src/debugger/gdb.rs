@@ -0,0 +1,426 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use cpu::CPU;
+use debugger::{Access, Debugger, Space, Stop, Watchpoint};
+use memory::CPUMemoryMap;
+
+// A GDB remote serial protocol server, so gdb-multiarch and other RSP clients can debug the
+// running game over TCP. The registers are, in order
+//   0 A, 1 X, 2 Y, 3 P, 4 SP   one byte each
+//   5 PC                       two bytes, little endian
+// Memory is the CPU's address space. Reads of the PPU, APU and controller registers return $FF
+// rather than disturb them, and only RAM and PRG-RAM can be written.
+
+// GDB's signal numbers, everything stops with a trap
+const SIGTRAP: u8 = 5;
+
+// Longest packet we take, in hex as qSupported reports it
+const PACKET_SIZE: usize = 0x1000;
+
+// Ctrl-C from the client
+const INTERRUPT: u8 = 0x03;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connection {
+    Open,
+    // The client detached or went away, the game should carry on without it
+    Detached,
+    // The client asked to kill the program
+    Killed,
+}
+
+enum Outcome {
+    Reply(String),
+    Resume,
+    Close(Connection),
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    pub debugger: Debugger,
+
+    // The client is waiting for a stop reply after a continue or step
+    running: bool,
+    no_ack: bool,
+
+    // A byte poll() read that wasn't a Ctrl-C, the start of the next packet
+    pending: Option<u8>,
+}
+
+impl GdbStub {
+    // Waits for a client to connect on localhost
+    pub fn listen(port: u16) -> io::Result<GdbStub> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (stream, _) = listener.accept()?;
+        Ok(GdbStub::new(stream))
+    }
+
+    // The CPU starts stopped until the client continues it
+    pub fn new(stream: TcpStream) -> GdbStub {
+        // Packets are tiny and each waits on the last, don't let them sit in the send buffer
+        let _ = stream.set_nodelay(true);
+
+        let mut debugger = Debugger::new();
+        debugger.pause();
+
+        GdbStub { stream: stream, debugger: debugger, running: false, no_ack: false, pending: None }
+    }
+
+    // Call before every instruction. Whenever execution stops this serves the client until it
+    // resumes, steps or disconnects.
+    pub fn check(&mut self, cpu: &mut CPU) -> io::Result<Connection> {
+        let stop = match self.debugger.check(cpu) {
+            Some(stop) => stop,
+            None => return Ok(Connection::Open),
+        };
+
+        if self.running {
+            self.running = false;
+            let reply = stop_reply(stop);
+            self.send(&reply)?;
+        }
+
+        loop {
+            let packet = match self.receive()? {
+                Some(packet) => packet,
+                None => return Ok(Connection::Detached),
+            };
+
+            match self.handle(cpu, &packet) {
+                Outcome::Reply(reply) => {
+                    self.send(&reply)?;
+                    if packet == "QStartNoAckMode" {
+                        self.no_ack = true;
+                    }
+                },
+                Outcome::Resume => {
+                    self.running = true;
+                    return Ok(Connection::Open);
+                },
+                Outcome::Close(connection) => return Ok(connection),
+            }
+        }
+    }
+
+    // Looks for a Ctrl-C from the client without blocking. Call it now and then, e.g. once a
+    // frame, so the client can stop the game while it's running. Anything else the client sent is
+    // kept for the next stop.
+    pub fn poll(&mut self) -> io::Result<Connection> {
+        if self.pending.is_some() {
+            return Ok(Connection::Open);
+        }
+
+        let mut byte = [0];
+        self.stream.set_nonblocking(true)?;
+        let result = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+
+        match result {
+            Ok(0) => Ok(Connection::Detached),
+            Ok(_) => {
+                if byte[0] == INTERRUPT {
+                    self.debugger.pause();
+                } else {
+                    self.pending = Some(byte[0]);
+                }
+                Ok(Connection::Open)
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(Connection::Open),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn handle(&mut self, cpu: &mut CPU, packet: &str) -> Outcome {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, |c| c.len_utf8()));
+
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+
+            "g" => {
+                let state = cpu.state();
+                hex(&[state.a, state.x, state.y, state.p, state.sp, state.pc as u8, (state.pc >> 8) as u8])
+            },
+
+            "G" => match unhex(args) {
+                Some(ref bytes) if bytes.len() == 7 => {
+                    let mut state = cpu.state();
+                    state.a = bytes[0];
+                    state.x = bytes[1];
+                    state.y = bytes[2];
+                    state.p = bytes[3];
+                    state.sp = bytes[4];
+                    state.pc = (bytes[6] as u16) << 8 | bytes[5] as u16;
+                    cpu.set_state(&state);
+                    "OK".to_string()
+                },
+                _ => error(),
+            },
+
+            "p" => {
+                let state = cpu.state();
+                match u8::from_str_radix(args, 16) {
+                    Ok(0) => hex(&[state.a]),
+                    Ok(1) => hex(&[state.x]),
+                    Ok(2) => hex(&[state.y]),
+                    Ok(3) => hex(&[state.p]),
+                    Ok(4) => hex(&[state.sp]),
+                    Ok(5) => hex(&[state.pc as u8, (state.pc >> 8) as u8]),
+                    _ => error(),
+                }
+            },
+
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                let register = parts.next().and_then(|register| u8::from_str_radix(register, 16).ok());
+                let value = parts.next().and_then(unhex);
+                let mut state = cpu.state();
+
+                match (register, value) {
+                    (Some(register), Some(ref value)) if register < 5 && value.len() == 1 => {
+                        match register {
+                            0 => state.a = value[0],
+                            1 => state.x = value[0],
+                            2 => state.y = value[0],
+                            3 => state.p = value[0],
+                            _ => state.sp = value[0],
+                        }
+                        cpu.set_state(&state);
+                        "OK".to_string()
+                    },
+                    (Some(5), Some(ref value)) if value.len() == 2 => {
+                        state.pc = (value[1] as u16) << 8 | value[0] as u16;
+                        cpu.set_state(&state);
+                        "OK".to_string()
+                    },
+                    _ => error(),
+                }
+            },
+
+            "m" => match parse_range(args) {
+                Some((address, length)) => {
+                    // Replies can be shorter than asked for, the client asks again for the rest
+                    let length = length.min((PACKET_SIZE - 4) / 2);
+                    let bytes: Vec<u8> = (0..length)
+                        .map(|i| cpu.memory.peek(address.wrapping_add(i as u16)))
+                        .collect();
+                    hex(&bytes)
+                },
+                None => error(),
+            },
+
+            "M" => {
+                let mut parts = args.splitn(2, ':');
+                let range = parts.next().and_then(parse_range);
+                let data = parts.next().and_then(unhex);
+
+                match (range, data) {
+                    (Some((address, length)), Some(data)) if data.len() == length => {
+                        // Write all of it or none of it
+                        let addresses = (0..length).map(|i| address.wrapping_add(i as u16));
+                        if addresses.clone().all(CPUMemoryMap::pokeable) {
+                            for (address, &byte) in addresses.zip(&data) {
+                                cpu.memory.poke(byte, address);
+                            }
+                            "OK".to_string()
+                        } else {
+                            error()
+                        }
+                    },
+                    _ => error(),
+                }
+            },
+
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(pc) => cpu.set_pc(pc),
+                        Err(_) => return Outcome::Reply(error()),
+                    }
+                }
+
+                if command == "c" {
+                    self.debugger.resume();
+                } else {
+                    self.debugger.step_into(1);
+                }
+                return Outcome::Resume;
+            },
+
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+
+            "H" => "OK".to_string(),
+
+            "k" => return Outcome::Close(Connection::Killed),
+
+            "D" => {
+                self.debugger.detach(cpu);
+                let _ = self.send("OK");
+                return Outcome::Close(Connection::Detached);
+            },
+
+            _ => match packet {
+                "qSupported" => format!("PacketSize={:x};QStartNoAckMode+", PACKET_SIZE),
+                "QStartNoAckMode" => "OK".to_string(),
+                "qAttached" => "1".to_string(),
+                _ if packet.starts_with("qSupported:") => format!("PacketSize={:x};QStartNoAckMode+", PACKET_SIZE),
+                // An empty reply tells the client we don't support the packet
+                _ => String::new(),
+            },
+        };
+
+        Outcome::Reply(reply)
+    }
+
+    // Z<type>,<address>,<kind> inserts and z removes. Types 0 and 1 are breakpoints, 2 to 4 are
+    // write, read and access watchpoints with kind being the length.
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.splitn(2, ',');
+        let kind = parts.next();
+        let range = parts.next().and_then(parse_range);
+
+        let (address, length) = match range {
+            Some(range) => range,
+            None => return error(),
+        };
+
+        let (read, write) = match kind {
+            Some("0") | Some("1") => {
+                if insert {
+                    if !self.debugger.breakpoints.contains(&address) {
+                        self.debugger.breakpoints.push(address);
+                    }
+                } else {
+                    self.debugger.breakpoints.retain(|&breakpoint| breakpoint != address);
+                }
+                return "OK".to_string();
+            },
+            Some("2") => (false, true),
+            Some("3") => (true, false),
+            Some("4") => (true, true),
+            _ => return String::new(),
+        };
+
+        // Anything longer than the whole address space is a mistake, shorter ones stop at $FFFF
+        if length == 0 || length > 0x10000 {
+            return error();
+        }
+
+        let watchpoint = Watchpoint {
+            space: Space::Cpu,
+            start: address,
+            end: (address as usize + length - 1).min(0xFFFF) as u16,
+            read: read,
+            write: write,
+        };
+
+        if insert {
+            self.debugger.add_watchpoint(watchpoint);
+        } else {
+            let index = self.debugger.watchpoints().iter().position(|&point| point == watchpoint);
+            if let Some(index) = index {
+                self.debugger.remove_watchpoint(index);
+            }
+        }
+
+        "OK".to_string()
+    }
+
+    // Reads the next packet, acknowledging it. None when the client has gone.
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip acks, and Ctrl-C since we're already stopped
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {},
+                    None => return Ok(None),
+                }
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+
+            let expected = String::from_utf8_lossy(&checksum);
+            let valid = u8::from_str_radix(&expected, 16).ok() == Some(checksum_of(&data));
+
+            if !self.no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    // The byte poll() held on to, if any, otherwise the next one from the client. None when the
+    // client has gone.
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+
+        let mut byte = [0];
+        if self.stream.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(byte[0]))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(self.stream, "${}#{:02x}", data, checksum_of(data.as_bytes()))?;
+        self.stream.flush()
+    }
+}
+
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Watchpoint(hit) => {
+            let kind = match hit.access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+            format!("T{:02x}{}:{:04x};", SIGTRAP, kind, hit.address)
+        },
+        _ => format!("S{:02x}", SIGTRAP),
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..text.len()).step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+// <address>,<length> in hex
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let mut parts = text.splitn(2, ',');
+    let address = parts.next().and_then(|address| u16::from_str_radix(address, 16).ok())?;
+    let length = parts.next().and_then(|length| usize::from_str_radix(length, 16).ok())?;
+    Some((address, length))
+}
@@ -0,0 +1,337 @@
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+
+use bus::Bus;
+use cpu::CPU;
+use disasm::disassemble;
+use debugger::{Access, Debugger, Space, Stop, Watchpoint};
+
+const HELP: &str = "\
+c, continue                          run until a breakpoint or watchpoint
+s, step [count]                      run count instructions, following JSRs and interrupts
+n, next                              run one instruction, or a whole subroutine if it's a JSR
+f, finish                            run until the current subroutine returns
+scanline <line>                      run until the PPU starts scanline line
+b, break [address]                   set a breakpoint, or list them
+d, delete <address>                  remove a breakpoint
+w, watch [ppu] <start>[-<end>] [r|w|rw]
+                                     stop on accesses to CPU (or PPU) memory, or list watchpoints
+unwatch <number>                     remove a watchpoint
+r, regs                              show the registers
+x [ppu] <address> [length]           dump CPU (or PPU) memory
+l, list [address] [count]            disassemble from address, or pc
+q, quit                              exit the emulator
+Addresses are in hex. An empty line repeats the last command.";
+
+enum Outcome {
+    // Wait for another command
+    Stay,
+    Resume,
+    Quit,
+}
+
+// A terminal front end for the debugger that works like a very small gdb
+pub struct Repl<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    last: String,
+}
+
+impl Repl<BufReader<Stdin>, Stdout> {
+    pub fn stdio() -> Repl<BufReader<Stdin>, Stdout> {
+        Repl::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    pub fn new(input: R, output: W) -> Repl<R, W> {
+        Repl { input: input, output: output, last: String::new() }
+    }
+
+    // Says why execution stopped then takes commands until one of them carries on. Returns false
+    // when the user quits or the input runs out.
+    pub fn prompt(&mut self, debugger: &mut Debugger, cpu: &mut CPU, stop: Stop) -> io::Result<bool> {
+        match stop {
+            Stop::Paused | Stop::Step => {},
+            Stop::Breakpoint => writeln!(self.output, "Breakpoint at ${:04X}", cpu.state().pc)?,
+            Stop::Watchpoint(hit) => {
+                let (space, access) = describe(hit.space, hit.access);
+                writeln!(self.output, "Watchpoint: {} {} of ${:02X} at ${:04X}", space, access, hit.data, hit.address)?;
+            },
+            Stop::Scanline => {
+                let (_, scanline, _) = cpu.memory.video_position();
+                writeln!(self.output, "Scanline {}", scanline)?;
+            },
+        }
+        writeln!(self.output, "{}", cpu.trace())?;
+
+        loop {
+            write!(self.output, "(nes) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+            self.last = line.clone();
+
+            match self.execute(debugger, cpu, &line)? {
+                Outcome::Stay => {},
+                Outcome::Resume => return Ok(true),
+                Outcome::Quit => return Ok(false),
+            }
+        }
+    }
+
+    fn execute(&mut self, debugger: &mut Debugger, cpu: &mut CPU, line: &str) -> io::Result<Outcome> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(Outcome::Stay),
+        };
+
+        match (command, args) {
+            ("c", []) | ("continue", []) => {
+                debugger.resume();
+                return Ok(Outcome::Resume);
+            },
+
+            ("s", _) | ("step", _) if args.len() <= 1 => {
+                match args.first().map(|count| count.parse::<u32>()) {
+                    None => debugger.step_into(1),
+                    Some(Ok(count)) if count > 0 => debugger.step_into(count),
+                    _ => return self.error("step count must be a positive number"),
+                }
+                return Ok(Outcome::Resume);
+            },
+
+            ("n", []) | ("next", []) => {
+                debugger.step_over(cpu);
+                return Ok(Outcome::Resume);
+            },
+
+            ("f", []) | ("finish", []) => {
+                debugger.step_out(cpu);
+                return Ok(Outcome::Resume);
+            },
+
+            ("scanline", [line]) => {
+                match line.parse::<u16>() {
+                    Ok(line) if line <= 261 => debugger.run_to_scanline(line),
+                    _ => return self.error("scanlines go from 0 to 261"),
+                }
+                return Ok(Outcome::Resume);
+            },
+
+            ("b", []) | ("break", []) => {
+                for address in &debugger.breakpoints {
+                    writeln!(self.output, "${:04X}", address)?;
+                }
+            },
+
+            ("b", [address]) | ("break", [address]) => {
+                match parse_address(address) {
+                    Some(address) => {
+                        if !debugger.breakpoints.contains(&address) {
+                            debugger.breakpoints.push(address);
+                        }
+                    },
+                    None => return self.error("not an address"),
+                }
+            },
+
+            ("d", [address]) | ("delete", [address]) => {
+                match parse_address(address) {
+                    Some(address) if debugger.breakpoints.contains(&address) => {
+                        debugger.breakpoints.retain(|&breakpoint| breakpoint != address);
+                    },
+                    _ => return self.error("no breakpoint there"),
+                }
+            },
+
+            ("w", []) | ("watch", []) => {
+                for (i, point) in debugger.watchpoints().iter().enumerate() {
+                    let (space, _) = describe(point.space, Access::Read);
+                    let access = match (point.read, point.write) {
+                        (true, true) => "rw",
+                        (true, false) => "r",
+                        _ => "w",
+                    };
+                    writeln!(self.output, "{}: {} ${:04X}-${:04X} {}", i, space, point.start, point.end, access)?;
+                }
+            },
+
+            ("w", _) | ("watch", _) => {
+                match parse_watchpoint(args) {
+                    Some(point) => debugger.add_watchpoint(point),
+                    None => return self.error("usage: watch [ppu] <start>[-<end>] [r|w|rw]"),
+                }
+            },
+
+            ("unwatch", [number]) => {
+                let removed = number.parse::<usize>().ok().and_then(|number| debugger.remove_watchpoint(number));
+                if removed.is_none() {
+                    return self.error("no watchpoint with that number");
+                }
+            },
+
+            ("r", []) | ("regs", []) => {
+                let state = cpu.state();
+                let (frame, scanline, dot) = cpu.memory.video_position();
+                writeln!(self.output, "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} {}",
+                         state.pc, state.a, state.x, state.y, state.sp, state.p, flags(state.p))?;
+                writeln!(self.output, "Frame {} scanline {} dot {}, cycle {}", frame, scanline, dot, state.cycles)?;
+            },
+
+            ("x", _) if !args.is_empty() => {
+                let (space, args) = match args[0] {
+                    "ppu" => (Space::Ppu, &args[1..]),
+                    _ => (Space::Cpu, args),
+                };
+                let start = args.first().and_then(|address| parse_address(address));
+                let length = match args.get(1) {
+                    Some(length) => length.parse::<u16>().ok(),
+                    None => Some(64),
+                };
+
+                match (start, length, args.len()) {
+                    (Some(start), Some(length), 1 ... 2) => self.dump(cpu, space, start, length)?,
+                    _ => return self.error("usage: x [ppu] <address> [length]"),
+                }
+            },
+
+            ("l", _) | ("list", _) if args.len() <= 2 => {
+                let start = match args.first() {
+                    Some(address) => parse_address(address),
+                    None => Some(cpu.state().pc),
+                };
+                let count = match args.get(1) {
+                    Some(count) => count.parse::<u32>().ok(),
+                    None => Some(10),
+                };
+
+                match (start, count) {
+                    (Some(start), Some(count)) => self.list(debugger, cpu, start, count)?,
+                    _ => return self.error("usage: list [address] [count]"),
+                }
+            },
+
+            ("h", []) | ("help", []) => writeln!(self.output, "{}", HELP)?,
+
+            ("q", []) | ("quit", []) => return Ok(Outcome::Quit),
+
+            _ => return self.error("unknown command, try help"),
+        }
+
+        Ok(Outcome::Stay)
+    }
+
+    fn error(&mut self, message: &str) -> io::Result<Outcome> {
+        writeln!(self.output, "{}", message)?;
+        Ok(Outcome::Stay)
+    }
+
+    // Sixteen bytes to a line. PPU addresses wrap at $3FFF like they do for PPUADDR.
+    fn dump(&mut self, cpu: &CPU, space: Space, start: u16, length: u16) -> io::Result<()> {
+        for line in 0..(length as u32).div_ceil(16) {
+            let address = start.wrapping_add(line as u16 * 16);
+            let count = (length as u32 - line * 16).min(16) as u16;
+            let bytes: Vec<String> = (0..count)
+                .map(|i| {
+                    let address = address.wrapping_add(i);
+                    match space {
+                        Space::Cpu => cpu.memory.peek(address),
+                        Space::Ppu => cpu.memory.ppu.peek_memory(address & 0x3FFF),
+                    }
+                })
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+
+            writeln!(self.output, "{:04X}: {}", address, bytes.join(" "))?;
+        }
+
+        Ok(())
+    }
+
+    // Marks pc with => and breakpoints with *
+    fn list(&mut self, debugger: &Debugger, cpu: &CPU, start: u16, count: u32) -> io::Result<()> {
+        let pc = cpu.state().pc;
+        let mut address = start;
+
+        for _ in 0..count {
+            let instruction = disassemble(&cpu.memory, address);
+            let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+            writeln!(self.output, "{}{} {:04X}  {:<8} {}",
+                     if address == pc { "=>" } else { "  " },
+                     if debugger.breakpoints.contains(&address) { '*' } else { ' ' },
+                     address, bytes.join(" "), instruction)?;
+
+            address = instruction.next();
+        }
+
+        Ok(())
+    }
+}
+
+fn describe(space: Space, access: Access) -> (&'static str, &'static str) {
+    let space = match space {
+        Space::Cpu => "CPU",
+        Space::Ppu => "PPU",
+    };
+    let access = match access {
+        Access::Read => "read",
+        Access::Write => "write",
+    };
+
+    (space, access)
+}
+
+// NV-BDIZC with the set flags in upper case
+fn flags(p: u8) -> String {
+    "nv-bdizc".chars().enumerate()
+        .map(|(i, flag)| if p & (0x80 >> i) != 0 { flag.to_ascii_uppercase() } else { flag })
+        .collect()
+}
+
+// Hex, with or without a leading $
+fn parse_address(address: &str) -> Option<u16> {
+    u16::from_str_radix(address.trim_start_matches('$'), 16).ok()
+}
+
+fn parse_watchpoint(args: &[&str]) -> Option<Watchpoint> {
+    let (space, args) = match args.first() {
+        Some(&"ppu") => (Space::Ppu, &args[1..]),
+        Some(&"cpu") => (Space::Cpu, &args[1..]),
+        _ => (Space::Cpu, args),
+    };
+
+    let (start, end) = match args.first() {
+        Some(range) => {
+            let mut ends = range.splitn(2, '-');
+            let start = parse_address(ends.next().unwrap())?;
+            match ends.next() {
+                Some(end) => (start, parse_address(end)?),
+                None => (start, start),
+            }
+        },
+        None => return None,
+    };
+
+    let (read, write) = match args.get(1) {
+        None | Some(&"rw") => (true, true),
+        Some(&"r") => (true, false),
+        Some(&"w") => (false, true),
+        _ => return None,
+    };
+
+    if start > end || args.len() > 2 {
+        return None;
+    }
+
+    Some(Watchpoint { space: space, start: start, end: end, read: read, write: write })
+}
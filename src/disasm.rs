@@ -0,0 +1,142 @@
+use std::fmt;
+
+use bus::Bus;
+use cpu::{AddressingMode, INSTRUCTIONS, is_unofficial};
+
+// A decoded instruction. Displays as assembly, e.g. LDA $0200,X
+pub struct Disassembly {
+    pub address: u16,
+    // The opcode followed by its operand
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub unofficial: bool,
+}
+
+// Decodes the instruction at address without any side effects on the bus
+pub fn disassemble<M: Bus>(bus: &M, address: u16) -> Disassembly {
+    let opcode = bus.peek(address);
+    let instruction = &INSTRUCTIONS[opcode as usize];
+
+    // KIL has no size since it jams the CPU, but it's still one byte long
+    let size = if instruction.size == 0 { 1 } else { instruction.size as u16 };
+
+    Disassembly {
+        address: address,
+        bytes: (0..size).map(|i| bus.peek(address.wrapping_add(i))).collect(),
+        mnemonic: instruction.str_name,
+        mode: instruction.addr_mode,
+        unofficial: is_unofficial(opcode),
+    }
+}
+
+impl Disassembly {
+    pub fn len(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    // The address of the next instruction
+    pub fn next(&self) -> u16 {
+        self.address.wrapping_add(self.len())
+    }
+
+    fn op8(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    fn op16(&self) -> u16 {
+        (self.bytes[2] as u16) << 8 | self.bytes[1] as u16
+    }
+
+    // The operand as written in assembly, empty for implied instructions
+    pub fn operand(&self) -> String {
+        match self.mode {
+            AddressingMode::Implicit => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", self.op8()),
+            AddressingMode::ZeroPage => format!("${:02X}", self.op8()),
+            AddressingMode::ZeroPageX => format!("${:02X},X", self.op8()),
+            AddressingMode::ZeroPageY => format!("${:02X},Y", self.op8()),
+            // Branches show where they go rather than the offset
+            AddressingMode::Relative => format!("${:04X}", self.next().wrapping_add(self.op8() as i8 as u16)),
+            AddressingMode::Absolute => format!("${:04X}", self.op16()),
+            AddressingMode::AbsoluteX => format!("${:04X},X", self.op16()),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", self.op16()),
+            AddressingMode::Indirect => format!("(${:04X})", self.op16()),
+            AddressingMode::IndexedIndirect => format!("(${:02X},X)", self.op8()),
+            AddressingMode::IndirectIndexed => format!("(${:02X}),Y", self.op8()),
+        }
+    }
+
+    // The address the instruction operates on given the index registers, if it touches memory
+    pub fn effective_address<M: Bus>(&self, bus: &M, x: u8, y: u8) -> Option<u16> {
+        let peek16_zero_page = |pointer: u8| {
+            (bus.peek(pointer.wrapping_add(1) as u16) as u16) << 8 | bus.peek(pointer as u16) as u16
+        };
+
+        match self.mode {
+            AddressingMode::Implicit | AddressingMode::Accumulator => None,
+            AddressingMode::Immediate => Some(self.address.wrapping_add(1)),
+            AddressingMode::ZeroPage => Some(self.op8() as u16),
+            AddressingMode::ZeroPageX => Some(self.op8().wrapping_add(x) as u16),
+            AddressingMode::ZeroPageY => Some(self.op8().wrapping_add(y) as u16),
+            AddressingMode::Relative => Some(self.next().wrapping_add(self.op8() as i8 as u16)),
+            AddressingMode::Absolute => Some(self.op16()),
+            AddressingMode::AbsoluteX => Some(self.op16().wrapping_add(x as u16)),
+            AddressingMode::AbsoluteY => Some(self.op16().wrapping_add(y as u16)),
+            // The high byte of the pointer comes from the same page, JMP ($02FF) reads $02FF and $0200
+            AddressingMode::Indirect => {
+                let pointer = self.op16();
+                let hi = bus.peek((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF)) as u16;
+                Some(hi << 8 | bus.peek(pointer) as u16)
+            },
+            AddressingMode::IndexedIndirect => Some(peek16_zero_page(self.op8().wrapping_add(x))),
+            AddressingMode::IndirectIndexed => Some(peek16_zero_page(self.op8()).wrapping_add(y as u16)),
+        }
+    }
+
+    // Where the operand points and what's there, the way Nintendulator annotates its traces, e.g.
+    // "@ 0210 = 3F" for LDA $0200,X. Empty when there's nothing to add to the operand.
+    pub fn annotation<M: Bus>(&self, bus: &M, x: u8, y: u8) -> String {
+        let address = match self.effective_address(bus, x, y) {
+            Some(address) => address,
+            None => return String::new(),
+        };
+        let value = bus.peek(address);
+
+        match self.mode {
+            AddressingMode::Implicit | AddressingMode::Accumulator |
+            AddressingMode::Immediate | AddressingMode::Relative => String::new(),
+            AddressingMode::ZeroPage => format!("= {:02X}", value),
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => format!("@ {:02X} = {:02X}", address, value),
+            AddressingMode::Absolute => match self.mnemonic {
+                // Jumps don't touch the memory they point at
+                "JMP" | "JSR" => String::new(),
+                _ => format!("= {:02X}", value),
+            },
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => format!("@ {:04X} = {:02X}", address, value),
+            AddressingMode::Indirect => format!("= {:04X}", address),
+            AddressingMode::IndexedIndirect => {
+                format!("@ {:02X} = {:04X} = {:02X}", self.op8().wrapping_add(x), address, value)
+            },
+            AddressingMode::IndirectIndexed => {
+                format!("= {:04X} @ {:04X} = {:02X}", address.wrapping_sub(y as u16), address, value)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = self.operand();
+        if operand.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, operand)
+        }
+    }
+}
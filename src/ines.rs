@@ -1,58 +1,482 @@
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::path::Path;
 
+// Everything that can go wrong while loading a ROM
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    // The file doesn't start with "NES\x1A"
+    BadMagic,
+    TruncatedHeader,
+    TruncatedTrainer,
+    // The file is shorter than the ROM sizes given in the header
+    TruncatedPrg { expected: usize, found: usize },
+    TruncatedChr { expected: usize, found: usize },
+    UnsupportedMapper(u16),
+    // Less PRG-ROM than the mapper's smallest board, including none at all
+    PrgTooSmall { mapper: u16, minimum: usize, found: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::Io(ref err) => write!(f, "couldn't read rom: {}", err),
+            RomError::BadMagic => write!(f, "not an iNES file"),
+            RomError::TruncatedHeader => write!(f, "file is too short to hold an iNES header"),
+            RomError::TruncatedTrainer => write!(f, "file is too short to hold its trainer"),
+            RomError::TruncatedPrg { expected, found } =>
+                write!(f, "expected {} bytes of PRG-ROM but only found {}", expected, found),
+            RomError::TruncatedChr { expected, found } =>
+                write!(f, "expected {} bytes of CHR-ROM but only found {}", expected, found),
+            RomError::UnsupportedMapper(mapper) => write!(f, "unsupported mapper: {}", mapper),
+            RomError::PrgTooSmall { mapper, minimum, found } =>
+                write!(f, "mapper {} needs at least {} bytes of PRG-ROM but only found {}", mapper, minimum, found),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RomError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(err: io::Error) -> RomError {
+        RomError::Io(err)
+    }
+}
+
+// Which revision of the header format the file uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeaderFormat {
+    // Bytes 7 - 15 may hold garbage (usually "DiskDude!") so only byte 6 can be trusted
+    Archaic,
+    INes,
+    Nes2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleType {
+    NES,
+    VsSystem,
+    Playchoice10,
+    // NES 2.0 extended console type from byte 13
+    Extended(u8),
+}
+
 pub struct INesInfo {
-    pub prgunits: u8,
-    pub chrunits: u8,
+    pub format: HeaderFormat,
+
+    // Sizes in 16k and 8k units. Files using the NES 2.0 exponent notation report 0 here and
+    // only the length of prgrom/chrrom is meaningful.
+    pub prgunits: u16,
+    pub chrunits: u16,
+
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: ::mapper::Mirroring,
+
+    // Flags 6 bit 1: PRG-RAM at $6000 - $7FFF is battery backed
+    pub battery: bool,
+
+    // Flags 6 bit 2: 512 bytes meant to be loaded at $7000, empty if there isn't one
+    pub trainer: Vec<u8>,
+
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+
+    // RAM sizes in bytes. Volatile and battery backed ("nv") RAM are listed separately.
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+
     pub prgrom: Vec<u8>,
     pub chrrom: Vec<u8>,
 }
 
 impl INesInfo {
-    pub fn new(filename: &String) -> INesInfo {
-        let mut result = read_file(filename);
-        if !check_header(&result) { panic!("Invalid nes file:"); }
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<INesInfo, RomError> {
+        let data = read_file(filename.as_ref())?;
+        INesInfo::from_bytes(&data)
+    }
+
+    pub fn from_bytes(result: &[u8]) -> Result<INesInfo, RomError> {
+        if result.len() < 4 || !check_header(result) { return Err(RomError::BadMagic); }
+        if result.len() < 16 { return Err(RomError::TruncatedHeader); }
+
+        let flags6 = result[6];
+        let flags7 = result[7];
+
+        let format = if flags7 & 0x0C == 0x08 {
+            HeaderFormat::Nes2
+        } else if flags7 & 0x0C == 0 && result[12..16].iter().all(|&b| b == 0) {
+            HeaderFormat::INes
+        } else {
+            HeaderFormat::Archaic
+        };
+
+        let mirroring = if flags6 & 0b1000 != 0 {
+            ::mapper::Mirroring::FourScreen
+        } else if flags6 & 1 == 1 {
+            ::mapper::Mirroring::Vertical
+        } else {
+            ::mapper::Mirroring::Horizontal
+        };
+        let battery = flags6 & 0b10 != 0;
+        let has_trainer = flags6 & 0b100 != 0;
+
+        let mut mapper = (flags6 >> 4) as u16;
+        let mut submapper = 0;
+        let mut prgunits = result[4] as u16;
+        let mut chrunits = result[5] as u16;
+        let mut prgsize = 0x4000 * prgunits as usize;
+        let mut chrsize = 0x2000 * chrunits as usize;
+        let mut timing = Timing::NTSC;
+        let mut console_type = ConsoleType::NES;
+        let mut prg_ram_size = 0x2000;
+        let mut prg_nvram_size = 0;
+        let mut chr_ram_size = if chrunits == 0 { 0x2000 } else { 0 };
+        let mut chr_nvram_size = 0;
+
+        match format {
+            HeaderFormat::Archaic => {}
+
+            HeaderFormat::INes => {
+                mapper |= (flags7 & 0xF0) as u16;
+                console_type = console_type_from(flags7 & 0b11);
+
+                // Byte 8 is the PRG-RAM size in 8k units, 0 meaning 8k for compatibility
+                if result[8] != 0 {
+                    prg_ram_size = 0x2000 * result[8] as usize;
+                }
+                if result[9] & 1 == 1 {
+                    timing = Timing::PAL;
+                }
+            }
+
+            HeaderFormat::Nes2 => {
+                mapper |= (flags7 & 0xF0) as u16 | ((result[8] & 0x0F) as u16) << 8;
+                submapper = result[8] >> 4;
+                console_type = console_type_from(flags7 & 0b11);
+                if console_type == ConsoleType::Extended(3) {
+                    console_type = ConsoleType::Extended(result[13] & 0x0F);
+                }
+
+                // The upper nibbles of the ROM sizes are in byte 9, 0xF selects exponent notation
+                let prg_msb = (result[9] & 0x0F) as u16;
+                let chr_msb = (result[9] >> 4) as u16;
+                if prg_msb == 0x0F {
+                    prgunits = 0;
+                    prgsize = exponent_size(result[4]);
+                } else {
+                    prgunits |= prg_msb << 8;
+                    prgsize = 0x4000 * prgunits as usize;
+                }
+                if chr_msb == 0x0F {
+                    chrunits = 0;
+                    chrsize = exponent_size(result[5]);
+                } else {
+                    chrunits |= chr_msb << 8;
+                    chrsize = 0x2000 * chrunits as usize;
+                }
+
+                prg_ram_size = shift_size(result[10] & 0x0F);
+                prg_nvram_size = shift_size(result[10] >> 4);
+                chr_ram_size = shift_size(result[11] & 0x0F);
+                chr_nvram_size = shift_size(result[11] >> 4);
+
+                timing = match result[12] & 0b11 {
+                    0 => Timing::NTSC,
+                    1 => Timing::PAL,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                };
+            }
+        }
 
-        let prgunits = result[4];
-        let chrunits = result[5];
+        // Battery backed PRG-RAM from an iNES header is the same RAM, just kept alive
+        if battery && format != HeaderFormat::Nes2 {
+            prg_nvram_size = prg_ram_size;
+            prg_ram_size = 0;
+        }
 
-        let prgsize = 0x4000 * prgunits as usize;
-        let chrsize = 0x2000 * chrunits as usize;
+        let mut offset = 16;
+        let trainer = if has_trainer {
+            if result.len() < offset + 512 { return Err(RomError::TruncatedTrainer); }
+            offset += 512;
+            result[16..offset].to_vec()
+        } else {
+            Vec::new()
+        };
 
-        let mut rest1 = result.split_off(16);
-        let rest2 = rest1.split_off(prgsize as usize);
-        let prgrom = rest1;
-        let chrrom = rest2;
+        let found = result.len() - offset;
+        if found < prgsize {
+            return Err(RomError::TruncatedPrg { expected: prgsize, found: found });
+        }
+        let prgrom = result[offset..offset + prgsize].to_vec();
+        offset += prgsize;
 
-        INesInfo{prgunits: prgunits, chrunits: chrunits, prgrom, chrrom: chrrom}
+        let found = result.len() - offset;
+        if found < chrsize {
+            return Err(RomError::TruncatedChr { expected: chrsize, found: found });
+        }
+        let chrrom = result[offset..offset + chrsize].to_vec();
+
+        Ok(INesInfo{
+            format: format,
+            prgunits: prgunits,
+            chrunits: chrunits,
+            mapper: mapper,
+            submapper: submapper,
+            mirroring: mirroring,
+            battery: battery,
+            trainer: trainer,
+            timing: timing,
+            console_type: console_type,
+            prg_ram_size: prg_ram_size,
+            prg_nvram_size: prg_nvram_size,
+            chr_ram_size: chr_ram_size,
+            chr_nvram_size: chr_nvram_size,
+            prgrom,
+            chrrom: chrrom,
+        })
     }
 }
 
-fn check_header(file: &Vec<u8>) -> bool {
+fn check_header(file: &[u8]) -> bool {
     file[0] == 0x4E && file[1] == 0x45 && file[2] == 0x53 && file[3] == 0x1A
 }
 
+fn console_type_from(bits: u8) -> ConsoleType {
+    match bits {
+        0 => ConsoleType::NES,
+        1 => ConsoleType::VsSystem,
+        2 => ConsoleType::Playchoice10,
+        _ => ConsoleType::Extended(3),
+    }
+}
+
+// NES 2.0 RAM sizes are stored as a shift count, 64 << n bytes or nothing at all for 0
+fn shift_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift as usize }
+}
+
+// NES 2.0 ROM sizes in exponent-multiplier notation, EEEEEEMM is 2^E * (MM*2 + 1) bytes. Sizes
+// too big to count couldn't fit in the file anyway, so they saturate and fail the length check.
+fn exponent_size(byte: u8) -> usize {
+    let exponent = (byte >> 2) as u32;
+    let multiplier = ((byte & 0b11) as usize) * 2 + 1;
+    1usize.checked_shl(exponent)
+        .and_then(|size| size.checked_mul(multiplier))
+        .unwrap_or(usize::max_value())
+}
 
-fn read_file(filename: &String) -> Vec<u8> {
-    let path = Path::new(filename);
-    let display = path.display();
 
+fn read_file(filename: &Path) -> Result<Vec<u8>, RomError> {
     let mut data:Vec<u8> = Vec::new();
 
-    // Open the path in read-only mode, returns `io::Result<File>`
-    let mut file = match File::open(&path) {
-        // The `description` method of `io::Error` returns a string that
-        // describes the error
-        Err(why) => panic!("couldn't open {}: {}", display,
-                           why.description()),
-        Ok(file) => file,
-    };
+    let mut file = File::open(filename)?;
+    file.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 16 byte header followed by prg and chr bytes of ROM
+    fn rom(header: &[u8], prg: usize, chr: usize) -> Vec<u8> {
+        let mut rom = b"NES\x1A".to_vec();
+        rom.extend_from_slice(header);
+        rom.resize(16, 0);
+        rom.resize(16 + prg, 0xEA);
+        rom.resize(16 + prg + chr, 0x55);
+        rom
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut data = rom(&[1, 1], 0x4000, 0x2000);
+        data[3] = 0;
+        match INesInfo::from_bytes(&data) {
+            Err(RomError::BadMagic) => {},
+            other => panic!("expected BadMagic, got {:?}", other.err()),
+        }
+
+        match INesInfo::from_bytes(b"NE") {
+            Err(RomError::BadMagic) => {},
+            other => panic!("expected BadMagic, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_prg_and_chr() {
+        match INesInfo::from_bytes(&rom(&[2, 1], 0x4000, 0)) {
+            Err(RomError::TruncatedPrg { expected: 0x8000, found: 0x4000 }) => {},
+            other => panic!("expected TruncatedPrg, got {:?}", other.err()),
+        }
+
+        match INesInfo::from_bytes(&rom(&[1, 1], 0x4000, 0x1000)) {
+            Err(RomError::TruncatedChr { expected: 0x2000, found: 0x1000 }) => {},
+            other => panic!("expected TruncatedChr, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn rejects_too_little_prg_for_the_mapper() {
+        // No PRG-ROM at all parses, but no board can run it
+        let rominfo = INesInfo::from_bytes(&rom(&[0, 1], 0, 0x2000)).unwrap();
+        match ::mapper::new(rominfo) {
+            Err(RomError::PrgTooSmall { mapper: 0, minimum: 0x4000, found: 0 }) => {},
+            other => panic!("expected PrgTooSmall, got {:?}", other.err()),
+        }
 
-    match file.read_to_end(&mut data) {
-        Err(why) => panic!("Couldn't read from {}: {}", display, why.description()),
-        Ok(_) => data
+        // AxROM switches 32k at a time
+        let rominfo = INesInfo::from_bytes(&rom(&[1, 0, 0x70], 0x4000, 0)).unwrap();
+        match ::mapper::new(rominfo) {
+            Err(RomError::PrgTooSmall { mapper: 7, minimum: 0x8000, found: 0x4000 }) => {},
+            other => panic!("expected PrgTooSmall, got {:?}", other.err()),
+        }
     }
 
+    #[test]
+    fn rejects_unsupported_mappers() {
+        let rominfo = INesInfo::from_bytes(&rom(&[2, 1, 0x50], 0x8000, 0x2000)).unwrap();
+        assert_eq!(rominfo.mapper, 5);
+        match ::mapper::new(rominfo) {
+            Err(RomError::UnsupportedMapper(5)) => {},
+            other => panic!("expected UnsupportedMapper, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn rejects_exponent_sizes_too_big_to_count() {
+        // NES 2.0 with PRG-ROM in exponent notation, 2^63 * 7 bytes
+        match INesInfo::from_bytes(&rom(&[0xFF, 0, 0, 0x08, 0, 0x0F], 0x4000, 0)) {
+            Err(RomError::TruncatedPrg { expected, found: 0x4000 }) => assert_eq!(expected, usize::max_value()),
+            other => panic!("expected TruncatedPrg, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn parses_an_ines_header() {
+        // Mapper 1 with vertical mirroring, a battery and 2 x 8k of PRG-RAM
+        let rominfo = INesInfo::from_bytes(&rom(&[2, 1, 0x13, 0x00, 2, 1], 0x8000, 0x2000)).unwrap();
+        assert_eq!(rominfo.format, HeaderFormat::INes);
+        assert_eq!(rominfo.mapper, 1);
+        assert_eq!(rominfo.mirroring, ::mapper::Mirroring::Vertical);
+        assert!(rominfo.battery);
+        assert_eq!(rominfo.timing, Timing::PAL);
+        assert_eq!(rominfo.prg_ram_size, 0);
+        assert_eq!(rominfo.prg_nvram_size, 0x4000);
+        assert_eq!(rominfo.prgrom.len(), 0x8000);
+        assert_eq!(rominfo.chrrom.len(), 0x2000);
+    }
+
+    #[test]
+    fn falls_back_to_archaic_headers_with_garbage_at_the_end() {
+        // "DiskDude!" over bytes 7 - 15 would otherwise put 0x40 in the mapper's high nibble
+        let mut data = rom(&[1, 1, 0x20], 0x4000, 0x2000);
+        data[7..16].copy_from_slice(b"DiskDude!");
+        let rominfo = INesInfo::from_bytes(&data).unwrap();
+        assert_eq!(rominfo.format, HeaderFormat::Archaic);
+        assert_eq!(rominfo.mapper, 2);
+        assert_eq!(rominfo.prg_ram_size, 0x2000);
+        assert_eq!(rominfo.timing, Timing::NTSC);
+    }
+
+    #[test]
+    fn parses_a_nes_2_header() {
+        let header = [
+            // 0x102 x 16k PRG-ROM, 0x001 x 8k CHR-ROM
+            0x02, 0x01,
+            // Mapper 0x154 submapper 3 on a Vs. System, four-screen
+            0x48, 0x59, 0x31, 0x01,
+            // 8k PRG-RAM, 32k PRG-NVRAM, 8k CHR-RAM, no CHR-NVRAM
+            0x97, 0x07,
+            // Dendy timing
+            0x03,
+        ];
+        let rominfo = INesInfo::from_bytes(&rom(&header, 0x102 * 0x4000, 0x2000)).unwrap();
+        assert_eq!(rominfo.format, HeaderFormat::Nes2);
+        assert_eq!(rominfo.mapper, 0x154);
+        assert_eq!(rominfo.submapper, 3);
+        assert_eq!(rominfo.console_type, ConsoleType::VsSystem);
+        assert_eq!(rominfo.mirroring, ::mapper::Mirroring::FourScreen);
+        assert_eq!(rominfo.prgunits, 0x102);
+        assert_eq!(rominfo.prgrom.len(), 0x102 * 0x4000);
+        assert_eq!(rominfo.prg_ram_size, 0x2000);
+        assert_eq!(rominfo.prg_nvram_size, 0x8000);
+        assert_eq!(rominfo.chr_ram_size, 0x2000);
+        assert_eq!(rominfo.chr_nvram_size, 0);
+        assert_eq!(rominfo.timing, Timing::Dendy);
+    }
+
+    #[test]
+    fn parses_nes_2_exponent_sizes() {
+        // PRG is 2^14 * 3 = 48k and CHR 2^10 * 1 = 1k
+        let header = [0b111001, 0b101000, 0x00, 0x08, 0, 0xFF];
+        let rominfo = INesInfo::from_bytes(&rom(&header, 0xC000, 0x400)).unwrap();
+        assert_eq!(rominfo.prgunits, 0);
+        assert_eq!(rominfo.chrunits, 0);
+        assert_eq!(rominfo.prgrom.len(), 0xC000);
+        assert_eq!(rominfo.chrrom.len(), 0x400);
+    }
+
+    #[test]
+    fn skips_the_trainer() {
+        let mut data = rom(&[1, 0, 0x04], 512 + 0x4000, 0);
+        for byte in &mut data[16..16 + 512] {
+            *byte = 0x77;
+        }
+        let rominfo = INesInfo::from_bytes(&data).unwrap();
+        assert_eq!(rominfo.trainer.len(), 512);
+        assert!(rominfo.trainer.iter().all(|&byte| byte == 0x77));
+        assert!(rominfo.prgrom.iter().all(|&byte| byte == 0xEA));
+
+        match INesInfo::from_bytes(&rom(&[1, 0, 0x04], 100, 0)) {
+            Err(RomError::TruncatedTrainer) => {},
+            other => panic!("expected TruncatedTrainer, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn sizes_prg_ram_from_the_header() {
+        // NES 2.0 NROM with no PRG-RAM at all
+        let rominfo = INesInfo::from_bytes(&rom(&[1, 1, 0x00, 0x08], 0x4000, 0x2000)).unwrap();
+        let mut mapper = ::mapper::new(rominfo).unwrap();
+        mapper.cpu_write(0x42, 0x6000);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+        assert!(mapper.prg_ram().is_empty());
+
+        // NES 2.0 MMC1 with 32k of battery backed PRG-RAM
+        let rominfo = INesInfo::from_bytes(&rom(&[1, 1, 0x12, 0x08, 0, 0, 0x90], 0x4000, 0x2000)).unwrap();
+        let mapper = ::mapper::new(rominfo).unwrap();
+        assert_eq!(mapper.prg_ram().len(), 0x8000);
+
+        // iNES headers can't say there's none, so NROM gets the usual 8k
+        let rominfo = INesInfo::from_bytes(&rom(&[1, 1], 0x4000, 0x2000)).unwrap();
+        let mut mapper = ::mapper::new(rominfo).unwrap();
+        mapper.cpu_write(0x42, 0x7FFF);
+        assert_eq!(mapper.cpu_read(0x7FFF), 0x42);
+        assert_eq!(mapper.prg_ram().len(), 0x2000);
+    }
 }
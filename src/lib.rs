@@ -1,4 +1,8 @@
 pub mod cpu;
+pub mod bus;
+pub mod disasm;
+pub mod trace;
+pub mod debugger;
 pub mod memory;
 pub mod cartridge;
 pub mod ppu;
@@ -6,3 +10,4 @@ pub mod apu;
 pub mod mapper;
 pub mod ines;
 pub mod controller;
+pub mod blargg;
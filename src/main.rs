@@ -6,30 +6,165 @@ use std::rc::Rc;
 use nes::ines::INesInfo;
 use nes::cartridge::Cartridge;
 use nes::ppu::PPU;
+use nes::apu::APU;
 use nes::memory::CPUMemoryMap;
 use nes::cpu::{CPU,Interrupt};
+use nes::trace::Tracer;
+use nes::debugger::Debugger;
+use nes::debugger::repl::Repl;
+use nes::debugger::gdb::{Connection, GdbStub};
 
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::audio::AudioSpecDesired;
 
-use std::{thread, time};
+use std::{io, thread, time};
+use std::path::{Path, PathBuf};
 
 use std::collections::HashSet;
+use std::ops::RangeInclusive;
 
 extern crate nes;
 
+const SAMPLE_RATE: i32 = 44100;
+
+// How many frames worth of audio we let pile up in the queue before waiting on it
+const AUDIO_FRAMES_QUEUED: u32 = 3;
+
+// Battery backed RAM is flushed to disk this often (about every 10 seconds) as well as on exit
+const SAVE_INTERVAL_FRAMES: u32 = 600;
+
+fn flush_save(cartridge: &mut Cartridge, save_file: &Path) {
+    if let Err(err) = cartridge.save_ram(save_file) {
+        println!("Couldn't save {}: {}", save_file.display(), err);
+    }
+}
+
+// Writes out everything that's buffered before exiting
+fn exit(cpu: &mut CPU, save_file: &Path, code: i32) -> ! {
+    flush_save(&mut cpu.memory.cart.borrow_mut(), save_file);
+    if let Some(tracer) = cpu.tracer() {
+        tracer.flush().unwrap_or_else(|err| println!("Couldn't write the trace log: {}", err));
+    }
+    std::process::exit(code)
+}
+
+// Drops the gdb stub once the client has gone, letting the game carry on
+fn gdb_connection(cpu: &mut CPU, gdb: &mut Option<GdbStub>, save_file: &Path, connection: Option<io::Result<Connection>>) {
+    match connection {
+        None | Some(Ok(Connection::Open)) => {},
+        Some(Ok(Connection::Killed)) => exit(cpu, save_file, 0),
+        Some(result) => {
+            if let Err(err) = result {
+                println!("Lost the gdb connection: {}", err);
+            }
+            if let Some(stub) = gdb.take() {
+                stub.debugger.detach(cpu);
+            }
+        },
+    }
+}
+
+fn usage(program: &String) -> ! {
+    println!("usage: {} [--video-sync] [--debug | --gdb <port>] [--save <save file>] [--trace <log file>] [--trace-pc <start>-<end>] [--trace-frames <start>-<end>] <rom file>", program);
+    std::process::exit(1);
+}
+
+// Parses an inclusive range like C000-C0FF, numbers in the given radix
+fn parse_range(range: &str, radix: u32) -> Option<(u64, u64)> {
+    let mut ends = range.splitn(2, '-').map(|end| u64::from_str_radix(end.trim_start_matches('$'), radix));
+    match (ends.next(), ends.next()) {
+        (Some(Ok(start)), Some(Ok(end))) if start <= end => Some((start, end)),
+        _ => None,
+    }
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() != 2 {
-        println!("usage: {} <rom file>", args[0]);
-        std::process::exit(1);
+    // By default the audio device paces emulation, --video-sync times frames with sleeps instead
+    let mut video_sync = false;
+    let mut debug = false;
+    let mut gdb_port: Option<u16> = None;
+    let mut save_file: Option<PathBuf> = None;
+    let mut rom_file: Option<String> = None;
+    let mut trace_file: Option<PathBuf> = None;
+    let mut trace_pcs: Option<RangeInclusive<u16>> = None;
+    let mut trace_frames: Option<RangeInclusive<u64>> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--video-sync" => video_sync = true,
+            "--debug" => debug = true,
+            "--gdb" => {
+                i += 1;
+                match args.get(i).map(|port| port.parse::<u16>()) {
+                    Some(Ok(port)) => gdb_port = Some(port),
+                    _ => usage(&args[0]),
+                }
+            },
+            "--save" => {
+                i += 1;
+                if i == args.len() { usage(&args[0]); }
+                save_file = Some(PathBuf::from(&args[i]));
+            },
+            "--trace" => {
+                i += 1;
+                if i == args.len() { usage(&args[0]); }
+                trace_file = Some(PathBuf::from(&args[i]));
+            },
+            "--trace-pc" => {
+                i += 1;
+                match args.get(i).and_then(|range| parse_range(range, 16)) {
+                    Some((start, end)) if end <= 0xFFFF => trace_pcs = Some(start as u16..=end as u16),
+                    _ => usage(&args[0]),
+                }
+            },
+            "--trace-frames" => {
+                i += 1;
+                match args.get(i).and_then(|range| parse_range(range, 10)) {
+                    Some((start, end)) => trace_frames = Some(start..=end),
+                    None => usage(&args[0]),
+                }
+            },
+            _ if rom_file.is_none() => rom_file = Some(args[i].clone()),
+            _ => usage(&args[0]),
+        }
+        i += 1;
+    }
+
+    let rom_file = match rom_file {
+        Some(rom_file) => rom_file,
+        None => usage(&args[0]),
+    };
+
+    if debug && gdb_port.is_some() {
+        usage(&args[0]);
+    }
+
+    let mut cartridge = match INesInfo::new(&rom_file).and_then(Cartridge::new) {
+        Ok(cartridge) => cartridge,
+        Err(err) => {
+            println!("Couldn't load {}: {}", rom_file, err);
+            std::process::exit(1);
+        }
+    };
+
+    // Saves live next to the rom as <rom>.sav unless told otherwise
+    let save_file = save_file.unwrap_or_else(|| Path::new(&rom_file).with_extension("sav"));
+    match cartridge.load_ram(&save_file) {
+        Err(ref err) if err.kind() != std::io::ErrorKind::NotFound => {
+            println!("Couldn't load {}: {}", save_file.display(), err);
+        },
+        _ => {}
     }
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
+    let audio_subsystem = sdl_context.audio().unwrap();
 
     let window = video_subsystem.window("NES Emulator", 512, 480)
         .position_centered()
@@ -37,9 +172,10 @@ fn main() {
         .build()
         .unwrap();
 
-    let mut renderer = window.renderer().build().unwrap();
+    let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
 
-    let mut texture = renderer.create_texture_streaming(
+    let mut texture = texture_creator.create_texture_streaming(
         PixelFormatEnum::RGB24, 256, 240).unwrap();
 
     texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
@@ -53,16 +189,69 @@ fn main() {
         }
     }).unwrap();
 
-    renderer.clear();
-    renderer.copy(&texture, None, None).unwrap();
-    renderer.present();
+    canvas.clear();
+    canvas.copy(&texture, None, None).unwrap();
+    canvas.present();
+
+    let desired_spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE),
+        channels: Some(1),
+        samples: Some(1024),
+    };
+    let audio_queue = audio_subsystem.open_queue::<f32, _>(None, &desired_spec).unwrap();
+    let sample_rate = audio_queue.spec().freq;
+    let frame_bytes = (sample_rate as u32 / 60) * 4;
+    audio_queue.resume();
 
-    let info = INesInfo::new(&args[1]);
-    let cartridge = Cartridge::new(info);
     let cartridge = Rc::new(RefCell::new(cartridge));
     let ppu = PPU::new(cartridge.clone());
-    let memory_map = CPUMemoryMap::new(cartridge, ppu);
+    let apu = APU::new(cartridge.clone());
+    let memory_map = CPUMemoryMap::new(cartridge, ppu, apu);
     let mut cpu = CPU::new(memory_map);
+    cpu.memory.apu.borrow_mut().set_sample_rate(sample_rate as u32);
+
+    if let Some(trace_file) = trace_file {
+        match Tracer::create(&trace_file) {
+            Ok(mut tracer) => {
+                tracer.pcs = trace_pcs;
+                tracer.frames = trace_frames;
+                cpu.set_tracer(Some(tracer));
+            },
+            Err(err) => {
+                println!("Couldn't create {}: {}", trace_file.display(), err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // The debugger starts paused at the reset vector, F11 breaks back into it
+    let mut debugger = if debug {
+        let mut debugger = Debugger::new();
+        debugger.attach(&mut cpu);
+        debugger.pause();
+        Some(debugger)
+    } else {
+        None
+    };
+    let mut repl = Repl::stdio();
+
+    // A gdb client gets the CPU stopped at the reset vector, the game waits until one connects
+    let mut gdb = match gdb_port {
+        Some(port) => {
+            println!("Waiting for gdb on localhost:{}", port);
+            match GdbStub::listen(port) {
+                Ok(stub) => {
+                    stub.debugger.attach(&mut cpu);
+                    Some(stub)
+                },
+                Err(err) => {
+                    println!("Couldn't listen on port {}: {}", port, err);
+                    std::process::exit(1);
+                }
+            }
+        },
+        None => None,
+    };
 
     let mut prev = time::Instant::now();
     let target = time::Duration::new(0,16666667);
@@ -72,8 +261,15 @@ fn main() {
     let mut keyarr1 = [false;8];
     let mut keyarr2 = [false;8];
 
+    let mut frames: u32 = 0;
+
     loop {
         if cpu.memory.ppu.nmi {
+            frames += 1;
+            if frames % SAVE_INTERVAL_FRAMES == 0 {
+                flush_save(&mut cpu.memory.cart.borrow_mut(), &save_file);
+            }
+
             texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
                 for y in 0..240 {
                     for x in 0..256 {
@@ -85,23 +281,54 @@ fn main() {
                 }
             }).unwrap();
 
-            renderer.clear();
-            renderer.copy(&texture, None, None).unwrap();
-            renderer.present();
-            let duration = prev.elapsed();
-            if duration < target {
-                thread::sleep(target - duration);
+            canvas.clear();
+            canvas.copy(&texture, None, None).unwrap();
+            canvas.present();
+
+            let connection = gdb.as_mut().map(|stub| stub.poll());
+            gdb_connection(&mut cpu, &mut gdb, &save_file, connection);
+
+            let samples = cpu.memory.apu.borrow_mut().take_samples();
+            audio_queue.queue_audio(&samples).unwrap();
+
+            if video_sync {
+                let duration = prev.elapsed();
+                if duration < target {
+                    thread::sleep(target - duration);
+                } else {
+                    println!("We missed a deadline");
+                }
+                prev = time::Instant::now();
+
+                // The video clock drifts from the audio clock so don't let latency build up
+                if audio_queue.size() > frame_bytes * AUDIO_FRAMES_QUEUED * 4 {
+                    audio_queue.clear();
+                }
             } else {
-                println!("We missed a deadline");
+                // Run ahead of the audio device by a few frames and no further
+                while audio_queue.size() > frame_bytes * AUDIO_FRAMES_QUEUED {
+                    thread::sleep(time::Duration::from_millis(1));
+                }
             }
-            prev = time::Instant::now();
 
 
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit {..}
                     | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                        std::process::exit(1)
+                        exit(&mut cpu, &save_file, 1)
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                        if let Some(ref mut debugger) = debugger {
+                            debugger.pause();
+                        }
+                    },
+                    // Pauses and resumes tracing
+                    Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                        if let Some(tracer) = cpu.tracer() {
+                            tracer.enabled = !tracer.enabled;
+                            println!("Tracing {}", if tracer.enabled { "resumed" } else { "paused" });
+                        }
                     },
                     _ => {}
                 }
@@ -130,10 +357,21 @@ fn main() {
                 keys.contains(&Keycode::D),  // Right
             ];
         }
+        if let Some(ref mut debugger) = debugger {
+            if let Some(stop) = debugger.check(&cpu) {
+                match repl.prompt(debugger, &mut cpu, stop) {
+                    Ok(true) => {},
+                    _ => exit(&mut cpu, &save_file, 0),
+                }
+            }
+        }
+
+        let connection = gdb.as_mut().map(|stub| stub.check(&mut cpu));
+        gdb_connection(&mut cpu, &mut gdb, &save_file, connection);
+
         cpu.memory.controller1.borrow_mut().set_all(keyarr1);
         cpu.memory.controller2.borrow_mut().set_all(keyarr2);
         let int = if (cpu.memory.ppu.nmi == true) { cpu.memory.ppu.nmi = false; Interrupt::IntNMI } else { Interrupt::IntNone };
-        let cycles = cpu.step(int);
-        cpu.memory.ppu.step(cycles*3);
+        cpu.step(int);
     }
 }
@@ -0,0 +1,192 @@
+pub mod nrom;
+pub mod mmc1;
+pub mod uxrom;
+pub mod cnrom;
+pub mod axrom;
+pub mod mmc3;
+
+// How the two physical nametables are laid out in the PPU's $2000-$2FFF window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    // Every nametable address maps to the same physical nametable
+    SingleScreenLower,
+    SingleScreenUpper,
+    // The cartridge supplies another 2k of VRAM so all four nametables are distinct
+    FourScreen,
+}
+
+// Pattern table memory on the cartridge. Boards with no CHR-ROM have CHR-RAM instead, which the
+// game fills in at runtime.
+pub struct ChrMemory {
+    data: Vec<u8>,
+    writable: bool,
+}
+
+impl ChrMemory {
+    pub fn new(chrrom: Vec<u8>, ram_size: usize) -> ChrMemory {
+        if chrrom.is_empty() {
+            // Plain iNES headers can't give a size so assume the usual 8k
+            let size = if ram_size == 0 { 0x2000 } else { ram_size };
+            ChrMemory {data: vec![0; size], writable: true}
+        } else {
+            ChrMemory {data: chrrom, writable: false}
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn read(&self, offset: usize) -> u8 {
+        self.data[offset % self.data.len()]
+    }
+
+    // Writes to CHR-ROM are ignored, just like on the real thing
+    pub fn write(&mut self, data: u8, offset: usize) {
+        if self.writable {
+            let len = self.data.len();
+            self.data[offset % len] = data;
+        }
+    }
+}
+
+// Work RAM at $6000 - $7FFF, sized from the header. RAM smaller than the 8k window is mirrored
+// through it and boards without any read back 0.
+pub struct PrgRam {
+    data: Box<[u8]>,
+}
+
+impl PrgRam {
+    pub fn new(size: usize) -> PrgRam {
+        PrgRam {data: vec![0; size].into_boxed_slice()}
+    }
+
+    pub fn read(&self, offset: usize) -> u8 {
+        if self.data.is_empty() { 0 } else { self.data[offset % self.data.len()] }
+    }
+
+    pub fn write(&mut self, data: u8, offset: usize) {
+        let len = self.data.len();
+        if len != 0 {
+            self.data[offset % len] = data;
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+// A cartridge board. Each mapper owns the ROM and RAM on the board and decides how the CPU and
+// PPU address spaces are mapped onto them.
+pub trait Mapper {
+    // $6000 - $FFFF on the CPU bus
+    fn cpu_read(&self, address: u16) -> u8;
+    fn cpu_write(&mut self, data: u8, address: u16);
+
+    // $0000 - $1FFF on the PPU bus
+    fn ppu_read(&self, address: u16) -> u8;
+    fn ppu_write(&mut self, data: u8, address: u16);
+
+    fn mirroring(&self) -> Mirroring;
+
+    // True while the board is holding the CPU's IRQ line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // PRG-RAM at $6000 - $7FFF, exposed so battery backed saves can be persisted
+    fn prg_ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    // Called with every address the PPU fetches from while rendering, so boards like MMC3 can
+    // watch A12 toggle between the pattern tables
+    fn watch_ppu_address(&mut self, _address: u16) {}
+}
+
+// Build the mapper selected by the iNES header
+pub fn new(rominfo: ::ines::INesInfo) -> Result<Box<dyn Mapper>, ::ines::RomError> {
+    // The banking maths needs at least one whole bank of PRG-ROM. AxROM switches 32k at a
+    // time, the rest fix a 16k (or two 8k) bank at the top of memory.
+    let minimum = match rominfo.mapper {
+        7 => 0x8000,
+        _ => 0x4000,
+    };
+    if rominfo.prgrom.len() < minimum {
+        return Err(::ines::RomError::PrgTooSmall { mapper: rominfo.mapper, minimum: minimum, found: rominfo.prgrom.len() });
+    }
+
+    let mapper: Box<dyn Mapper> = match rominfo.mapper {
+        0 => Box::new(nrom::NROM::new(rominfo)),
+        1 => Box::new(mmc1::MMC1::new(rominfo)),
+        2 => Box::new(uxrom::UxROM::new(rominfo)),
+        3 => Box::new(cnrom::CNROM::new(rominfo)),
+        4 => Box::new(mmc3::MMC3::new(rominfo)),
+        7 => Box::new(axrom::AxROM::new(rominfo)),
+        _ => return Err(::ines::RomError::UnsupportedMapper(rominfo.mapper)),
+    };
+    Ok(mapper)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use mapper::nrom::NROM;
+
+    // A ROM for the mapper tests, prg and chr are sizes in bytes. Every byte of PRG-ROM holds the
+    // number of its 8k bank and every byte of CHR-ROM the number of its 1k bank, so a read shows
+    // what's mapped in.
+    pub fn rominfo(mapper: u8, flags6: u8, prg: usize, chr: usize) -> ::ines::INesInfo {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, (prg / 0x4000) as u8, (chr / 0x2000) as u8,
+                           (mapper << 4) | flags6, mapper & 0xF0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.extend((0..prg).map(|offset| (offset / 0x2000) as u8));
+        rom.extend((0..chr).map(|offset| (offset / 0x400) as u8));
+        ::ines::INesInfo::from_bytes(&rom).unwrap()
+    }
+
+    #[test]
+    fn boards_without_chr_rom_get_writable_chr_ram() {
+        let rominfo = rominfo(0, 0, 0x4000, 0);
+        assert_eq!(rominfo.chrunits, 0);
+
+        let mut nrom = NROM::new(rominfo);
+        nrom.ppu_write(0x12, 0x0000);
+        nrom.ppu_write(0x34, 0x1FFF);
+
+        assert_eq!(nrom.ppu_read(0x0000), 0x12);
+        assert_eq!(nrom.ppu_read(0x1FFF), 0x34);
+    }
+
+    #[test]
+    fn chr_ram_takes_its_size_from_the_header_or_defaults_to_8k() {
+        assert_eq!(ChrMemory::new(Vec::new(), 0).len(), 0x2000);
+        assert_eq!(ChrMemory::new(Vec::new(), 0x8000).len(), 0x8000);
+        assert!(!ChrMemory::new(Vec::new(), 0).is_empty());
+    }
+
+    #[test]
+    fn writes_to_chr_rom_are_ignored() {
+        let mut nrom = NROM::new(rominfo(0, 0, 0x4000, 0x2000));
+        nrom.ppu_write(0x12, 0x0400);
+        assert_eq!(nrom.ppu_read(0x0400), 1);
+
+        let mut chr = ChrMemory::new(vec![0x55; 0x2000], 0);
+        chr.write(0x12, 0);
+        assert_eq!(chr.read(0), 0x55);
+    }
+}
@@ -0,0 +1,80 @@
+use mapper::{ChrMemory, Mapper, Mirroring};
+
+// Mapper 7. A switchable 32k PRG bank and a register bit that picks which nametable is shown
+// on every screen.
+pub struct AxROM {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+
+    prg_bank: u8,
+    upper_nametable: bool,
+}
+
+impl AxROM {
+    pub fn new(rominfo: ::ines::INesInfo) -> AxROM {
+        AxROM {prgrom: rominfo.prgrom, chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size), prg_bank: 0, upper_nametable: false}
+    }
+}
+
+impl Mapper for AxROM {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            0x8000 ... 0xFFFF => {
+                let prg_banks = self.prgrom.len() / 0x8000;
+                let bank = self.prg_bank as usize % prg_banks;
+                self.prgrom[bank * 0x8000 + (address as usize - 0x8000)]
+            }
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        if address >= 0x8000 {
+            self.prg_bank = data & 0b111;
+            self.upper_nametable = (data >> 4) & 0b1 == 1;
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        self.chr.write(data, address as usize);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.upper_nametable { Mirroring::SingleScreenUpper } else { Mirroring::SingleScreenLower }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    #[test]
+    fn switches_32k_of_prg() {
+        // 4 banks of 32k
+        let mut axrom = AxROM::new(rominfo(7, 0, 0x20000, 0));
+        assert_eq!(axrom.cpu_read(0x8000), 0);
+        assert_eq!(axrom.cpu_read(0xFFFF), 3);
+
+        axrom.cpu_write(2, 0x8000);
+        assert_eq!(axrom.cpu_read(0x8000), 8);
+        assert_eq!(axrom.cpu_read(0xFFFF), 11);
+    }
+
+    #[test]
+    fn bit_4_selects_the_single_screen_nametable() {
+        let mut axrom = AxROM::new(rominfo(7, 0, 0x20000, 0));
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.cpu_write(0x10, 0x8000);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+        assert_eq!(axrom.cpu_read(0x8000), 0);
+
+        axrom.cpu_write(0x01, 0x8000);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+    }
+}
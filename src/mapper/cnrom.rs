@@ -0,0 +1,73 @@
+use mapper::{ChrMemory, Mapper, Mirroring};
+
+// Mapper 3. Fixed PRG-ROM like NROM with a switchable 8k CHR bank.
+pub struct CNROM {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+    mirroring: Mirroring,
+
+    chr_bank: u8,
+}
+
+impl CNROM {
+    pub fn new(rominfo: ::ines::INesInfo) -> CNROM {
+        CNROM {prgrom: rominfo.prgrom, chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size), mirroring: rominfo.mirroring, chr_bank: 0}
+    }
+}
+
+impl CNROM {
+    fn chr_offset(&self) -> usize {
+        let chr_banks = ::std::cmp::max(self.chr.len() / 0x2000, 1);
+        (self.chr_bank as usize % chr_banks) * 0x2000
+    }
+}
+
+impl Mapper for CNROM {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            0x8000 ... 0xFFFF => self.prgrom[(address as usize - 0x8000) % self.prgrom.len()],
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        if address >= 0x8000 {
+            self.chr_bank = data;
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(self.chr_offset() + address as usize)
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        let offset = self.chr_offset() + address as usize;
+        self.chr.write(data, offset);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    #[test]
+    fn switches_8k_of_chr() {
+        // 32k of PRG-ROM and 4 banks of 8k CHR-ROM
+        let mut cnrom = CNROM::new(rominfo(3, 0, 0x8000, 0x8000));
+        assert_eq!(cnrom.ppu_read(0x0000), 0);
+
+        cnrom.cpu_write(2, 0x8000);
+        assert_eq!(cnrom.ppu_read(0x0000), 16);
+        assert_eq!(cnrom.ppu_read(0x1FFF), 23);
+        assert_eq!(cnrom.cpu_read(0x8000), 0);
+        assert_eq!(cnrom.cpu_read(0xFFFF), 3);
+
+        cnrom.cpu_write(5, 0xFFFF);
+        assert_eq!(cnrom.ppu_read(0x0000), 8);
+    }
+}
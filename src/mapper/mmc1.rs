@@ -0,0 +1,285 @@
+use mapper::{ChrMemory, Mapper, Mirroring, PrgRam};
+
+// Mapper 1. Registers are loaded one bit at a time through a serial shift register and control
+// 16k/32k PRG banking, 4k/8k CHR banking and mirroring.
+pub struct MMC1 {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+    prgram: PrgRam,
+
+    // Serial port
+    shift_register: u8,
+    shift_count: u8,
+
+    // $8000 - $9FFF
+    control: u8,
+    // $A000 - $BFFF
+    chr_bank_0: u8,
+    // $C000 - $DFFF
+    chr_bank_1: u8,
+    // $E000 - $FFFF
+    prg_bank: u8,
+
+    // Byte offsets of the two 16k PRG windows and the two 4k CHR windows
+    prg_offsets: [usize; 2],
+    chr_offsets: [usize; 2],
+}
+
+impl MMC1 {
+    pub fn new(rominfo: ::ines::INesInfo) -> MMC1 {
+        let mut mmc1 = MMC1 {
+            prgrom: rominfo.prgrom,
+            chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size),
+            prgram: PrgRam::new(rominfo.prg_ram_size + rominfo.prg_nvram_size),
+            shift_register: 0,
+            shift_count: 0,
+            // Power on with the last bank fixed at $C000
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            prg_offsets: [0; 2],
+            chr_offsets: [0; 2],
+        };
+        mmc1.update_offsets();
+        mmc1
+    }
+
+    fn write_register(&mut self, data: u8, address: u16) {
+        match address {
+            0x8000 ..= 0x9FFF => self.control = data,
+            0xA000 ..= 0xBFFF => self.chr_bank_0 = data,
+            0xC000 ..= 0xDFFF => self.chr_bank_1 = data,
+            0xE000 ..= 0xFFFF => self.prg_bank = data,
+            _ => panic!("MMC1 register write outside of $8000 - $FFFF")
+        }
+        self.update_offsets();
+    }
+
+    fn update_offsets(&mut self) {
+        let prg_banks = self.prgrom.len() / 0x4000;
+
+        // 512k boards (SUROM) use a CHR bank bit to pick which 256k half of PRG-ROM is visible
+        let prg_outer = if prg_banks > 16 { self.chr_bank_0 as usize & 0x10 } else { 0 };
+        let bank = prg_outer | (self.prg_bank as usize & 0x0F);
+        let last = prg_outer | ((prg_banks - 1) & 0x0F);
+
+        let (first, second) = match (self.control >> 2) & 0b11 {
+            // Switch 32k at $8000, ignoring the low bit of the bank number
+            0 | 1 => (bank & !1, bank | 1),
+            // Fix the first bank at $8000 and switch 16k at $C000
+            2 => (prg_outer, bank),
+            // Fix the last bank at $C000 and switch 16k at $8000
+            _ => (bank, last),
+        };
+        self.prg_offsets = [(first % prg_banks) * 0x4000, (second % prg_banks) * 0x4000];
+
+        let chr_banks = ::std::cmp::max(self.chr.len() / 0x1000, 1);
+        let (first, second) = if (self.control >> 4) & 0b1 == 0 {
+            // Switch 8k at a time, ignoring the low bit of the bank number
+            (self.chr_bank_0 as usize & !1, self.chr_bank_0 as usize | 1)
+        } else {
+            (self.chr_bank_0 as usize, self.chr_bank_1 as usize)
+        };
+        self.chr_offsets = [(first % chr_banks) * 0x1000, (second % chr_banks) * 0x1000];
+    }
+
+    fn prgram_enabled(&self) -> bool {
+        (self.prg_bank >> 4) & 0b1 == 0
+    }
+}
+
+impl Mapper for MMC1 {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            0x6000 ..= 0x7FFF if self.prgram_enabled() => self.prgram.read(address as usize - 0x6000),
+            0x8000 ..= 0xBFFF => self.prgrom[self.prg_offsets[0] + (address as usize - 0x8000)],
+            0xC000 ..= 0xFFFF => self.prgrom[self.prg_offsets[1] + (address as usize - 0xC000)],
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        match address {
+            0x6000 ..= 0x7FFF if self.prgram_enabled() => self.prgram.write(data, address as usize - 0x6000),
+            0x8000 ..= 0xFFFF => {
+                // Writing a value with bit 7 set resets the shift register
+                if data & 0x80 != 0 {
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    self.update_offsets();
+                    return;
+                }
+
+                self.shift_register |= (data & 1) << self.shift_count;
+                self.shift_count += 1;
+
+                // The fifth write copies the shift register into the register picked by the address
+                if self.shift_count == 5 {
+                    let value = self.shift_register;
+                    self.write_register(value, address);
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        let window = (address / 0x1000) as usize;
+        self.chr.read(self.chr_offsets[window] + (address as usize % 0x1000))
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        let window = (address / 0x1000) as usize;
+        let offset = self.chr_offsets[window] + (address as usize % 0x1000);
+        self.chr.write(data, offset);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prgram.data()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prgram.data_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    // Loads a register through the serial port, low bit first
+    fn load(mmc1: &mut MMC1, address: u16, value: u8) {
+        for i in 0..5 {
+            mmc1.cpu_write((value >> i) & 1, address);
+        }
+    }
+
+    // 128k of PRG-ROM and 128k of CHR-ROM
+    fn mmc1() -> MMC1 {
+        MMC1::new(rominfo(1, 0, 0x20000, 0x20000))
+    }
+
+    #[test]
+    fn registers_load_on_the_fifth_write() {
+        let mut mmc1 = mmc1();
+        for i in 0..4 {
+            mmc1.cpu_write((3 >> i) & 1, 0xE000);
+            assert_eq!(mmc1.cpu_read(0x8000), 0);
+        }
+
+        mmc1.cpu_write(0, 0xE000);
+        assert_eq!(mmc1.cpu_read(0x8000), 6);
+    }
+
+    #[test]
+    fn bit_7_resets_the_shift_register_and_fixes_the_last_bank() {
+        let mut mmc1 = mmc1();
+        load(&mut mmc1, 0x8000, 0x08);
+        assert_eq!(mmc1.cpu_read(0xC000), 0);
+
+        // Three bits in, then a reset throws them away
+        for _ in 0..3 {
+            mmc1.cpu_write(1, 0xE000);
+        }
+        mmc1.cpu_write(0x80, 0x8000);
+        assert_eq!(mmc1.cpu_read(0xC000), 14);
+
+        load(&mut mmc1, 0xE000, 2);
+        assert_eq!(mmc1.cpu_read(0x8000), 4);
+        assert_eq!(mmc1.cpu_read(0xC000), 14);
+    }
+
+    #[test]
+    fn prg_modes_0_and_1_switch_32k_ignoring_the_low_bit() {
+        for &control in &[0x00, 0x04] {
+            let mut mmc1 = mmc1();
+            load(&mut mmc1, 0x8000, control);
+            load(&mut mmc1, 0xE000, 3);
+
+            assert_eq!(mmc1.cpu_read(0x8000), 4);
+            assert_eq!(mmc1.cpu_read(0xC000), 6);
+            assert_eq!(mmc1.cpu_read(0xFFFF), 7);
+        }
+    }
+
+    #[test]
+    fn prg_mode_2_fixes_the_first_bank_at_8000() {
+        let mut mmc1 = mmc1();
+        load(&mut mmc1, 0x8000, 0x08);
+        load(&mut mmc1, 0xE000, 3);
+
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 6);
+    }
+
+    #[test]
+    fn prg_mode_3_fixes_the_last_bank_at_c000() {
+        let mut mmc1 = mmc1();
+        load(&mut mmc1, 0x8000, 0x0C);
+        load(&mut mmc1, 0xE000, 3);
+
+        assert_eq!(mmc1.cpu_read(0x8000), 6);
+        assert_eq!(mmc1.cpu_read(0xC000), 14);
+    }
+
+    #[test]
+    fn chr_switches_8k_ignoring_the_low_bit() {
+        let mut mmc1 = mmc1();
+        load(&mut mmc1, 0x8000, 0x0C);
+        load(&mut mmc1, 0xA000, 3);
+        load(&mut mmc1, 0xC000, 5);
+
+        assert_eq!(mmc1.ppu_read(0x0000), 8);
+        assert_eq!(mmc1.ppu_read(0x1000), 12);
+    }
+
+    #[test]
+    fn chr_switches_two_4k_banks() {
+        let mut mmc1 = mmc1();
+        load(&mut mmc1, 0x8000, 0x1C);
+        load(&mut mmc1, 0xA000, 3);
+        load(&mut mmc1, 0xC000, 5);
+
+        assert_eq!(mmc1.ppu_read(0x0000), 12);
+        assert_eq!(mmc1.ppu_read(0x1000), 20);
+    }
+
+    #[test]
+    fn surom_picks_the_256k_half_with_chr_bank_0() {
+        let mut mmc1 = MMC1::new(rominfo(1, 0, 0x80000, 0));
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 30);
+
+        load(&mut mmc1, 0xA000, 0x10);
+        assert_eq!(mmc1.cpu_read(0x8000), 32);
+        assert_eq!(mmc1.cpu_read(0xC000), 62);
+
+        load(&mut mmc1, 0xE000, 1);
+        assert_eq!(mmc1.cpu_read(0x8000), 34);
+    }
+
+    #[test]
+    fn control_selects_the_mirroring() {
+        let mut mmc1 = mmc1();
+        let modes = [Mirroring::SingleScreenLower, Mirroring::SingleScreenUpper,
+                     Mirroring::Vertical, Mirroring::Horizontal];
+        for (value, &mirroring) in modes.iter().enumerate() {
+            load(&mut mmc1, 0x8000, 0x0C | value as u8);
+            assert_eq!(mmc1.mirroring(), mirroring);
+        }
+    }
+}
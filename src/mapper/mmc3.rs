@@ -0,0 +1,311 @@
+use mapper::{ChrMemory, Mapper, Mirroring, PrgRam};
+
+// Number of consecutive fetches with A12 low before a rise clocks the scanline counter. This
+// stands in for the M2 based filter on the real chip, which ignores the short drops of A12
+// between background tile fetches.
+const A12_FILTER: u8 = 3;
+
+// Mapper 4. 8k PRG banks, 1k/2k CHR banks and a scanline counter that raises an IRQ.
+pub struct MMC3 {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+    prgram: PrgRam,
+
+    // $8000 - Bank select
+    bank_select: u8,
+    prg_mode: bool,
+    chr_inversion: bool,
+
+    // $8001 - Bank data, R0 - R7
+    registers: [u8; 8],
+
+    // $A000 - Mirroring
+    mirroring: Mirroring,
+
+    // $A001 - PRG-RAM protect
+    prgram_enabled: bool,
+    prgram_write_protect: bool,
+
+    // $C000 - $E001 - Scanline counter
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq: bool,
+
+    // How many PPU fetches in a row have had A12 low
+    a12_low_count: u8,
+
+    // Byte offsets of the four 8k PRG windows and the eight 1k CHR windows
+    prg_offsets: [usize; 4],
+    chr_offsets: [usize; 8],
+}
+
+impl MMC3 {
+    pub fn new(rominfo: ::ines::INesInfo) -> MMC3 {
+        let mut mmc3 = MMC3 {
+            prgrom: rominfo.prgrom,
+            chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size),
+            prgram: PrgRam::new(rominfo.prg_ram_size + rominfo.prg_nvram_size),
+            bank_select: 0,
+            prg_mode: false,
+            chr_inversion: false,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: rominfo.mirroring,
+            prgram_enabled: true,
+            prgram_write_protect: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq: false,
+            a12_low_count: 0,
+            prg_offsets: [0; 4],
+            chr_offsets: [0; 8],
+        };
+        mmc3.update_offsets();
+        mmc3
+    }
+
+    fn update_offsets(&mut self) {
+        let prg_banks = self.prgrom.len() / 0x2000;
+        let second_last = prg_banks - 2;
+        let last = prg_banks - 1;
+        let r6 = self.registers[6] as usize % prg_banks;
+        let r7 = self.registers[7] as usize % prg_banks;
+
+        let prg = if self.prg_mode {
+            [second_last, r7, r6, last]
+        } else {
+            [r6, r7, second_last, last]
+        };
+        for i in 0..4 {
+            self.prg_offsets[i] = prg[i] * 0x2000;
+        }
+
+        let chr_banks = ::std::cmp::max(self.chr.len() / 0x400, 1);
+        let r = |i: usize| self.registers[i] as usize;
+
+        // R0 and R1 select 2k banks so their low bit is ignored
+        let chr = [r(0) & !1, r(0) | 1, r(1) & !1, r(1) | 1, r(2), r(3), r(4), r(5)];
+        let mut chr_offsets = [0; 8];
+        for i in 0..8 {
+            // Inversion swaps the 2k and 1k halves of the pattern tables
+            let window = if self.chr_inversion { i ^ 4 } else { i };
+            chr_offsets[window] = (chr[i] % chr_banks) * 0x400;
+        }
+        self.chr_offsets = chr_offsets;
+    }
+
+    fn clock_scanline_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq = true;
+        }
+    }
+}
+
+impl Mapper for MMC3 {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            0x6000 ... 0x7FFF => {
+                if self.prgram_enabled { self.prgram.read(address as usize - 0x6000) } else { 0 }
+            }
+            0x8000 ... 0xFFFF => {
+                let window = (address as usize - 0x8000) / 0x2000;
+                self.prgrom[self.prg_offsets[window] + (address as usize % 0x2000)]
+            }
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        let even = address & 1 == 0;
+        match address {
+            0x6000 ... 0x7FFF => {
+                if self.prgram_enabled && !self.prgram_write_protect {
+                    self.prgram.write(data, address as usize - 0x6000);
+                }
+            }
+            0x8000 ... 0x9FFF => {
+                if even {
+                    self.bank_select   = data & 0b111;
+                    self.prg_mode      = (data >> 6) & 0b1 == 1;
+                    self.chr_inversion = (data >> 7) & 0b1 == 1;
+                } else {
+                    self.registers[self.bank_select as usize] = data;
+                }
+                self.update_offsets();
+            }
+            0xA000 ... 0xBFFF => {
+                if even {
+                    // Boards wired for four-screen ignore the mirroring register
+                    if self.mirroring != Mirroring::FourScreen {
+                        self.mirroring = if data & 1 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+                    }
+                } else {
+                    self.prgram_write_protect = (data >> 6) & 0b1 == 1;
+                    self.prgram_enabled       = (data >> 7) & 0b1 == 1;
+                }
+            }
+            0xC000 ... 0xDFFF => {
+                if even {
+                    self.irq_latch = data;
+                } else {
+                    self.irq_counter = 0;
+                    self.irq_reload = true;
+                }
+            }
+            0xE000 ... 0xFFFF => {
+                if even {
+                    // Disabling also acknowledges a pending interrupt
+                    self.irq_enabled = false;
+                    self.irq = false;
+                } else {
+                    self.irq_enabled = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        let window = address as usize / 0x400;
+        self.chr.read(self.chr_offsets[window] + (address as usize % 0x400))
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        let window = address as usize / 0x400;
+        let offset = self.chr_offsets[window] + (address as usize % 0x400);
+        self.chr.write(data, offset);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prgram.data()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prgram.data_mut()
+    }
+
+    fn watch_ppu_address(&mut self, address: u16) {
+        if address & 0x1000 != 0 {
+            if self.a12_low_count >= A12_FILTER {
+                self.clock_scanline_counter();
+            }
+            self.a12_low_count = 0;
+        } else if self.a12_low_count < A12_FILTER {
+            self.a12_low_count += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    fn mmc3() -> MMC3 {
+        MMC3::new(rominfo(4, 0, 0x20000, 0x20000))
+    }
+
+    // The PPU fetching from the left pattern table for a while and then from the right one, the
+    // way it does once per scanline with background at $0000 and sprites at $1000
+    fn scanline(mmc3: &mut MMC3) {
+        for _ in 0..A12_FILTER {
+            mmc3.watch_ppu_address(0x0000);
+        }
+        mmc3.watch_ppu_address(0x1000);
+    }
+
+    #[test]
+    fn counter_reloads_from_the_latch_and_fires_at_zero() {
+        let mut mmc3 = mmc3();
+        mmc3.cpu_write(3, 0xC000);
+        mmc3.cpu_write(0, 0xC001);
+        mmc3.cpu_write(0, 0xE001);
+
+        // The first clock loads 3, the next three count down to 0
+        for _ in 0..3 {
+            scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn writing_the_reload_register_restarts_the_count() {
+        let mut mmc3 = mmc3();
+        mmc3.cpu_write(2, 0xC000);
+        mmc3.cpu_write(0, 0xE001);
+
+        // Load 2 and count down to 1, then reload part way through
+        scanline(&mut mmc3);
+        scanline(&mut mmc3);
+        mmc3.cpu_write(0, 0xC001);
+
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq());
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq());
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn disabling_acknowledges_and_enabling_arms_again() {
+        let mut mmc3 = mmc3();
+
+        // With a latch of 0 every clock reloads 0 and would fire
+        mmc3.cpu_write(0, 0xC000);
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq());
+
+        mmc3.cpu_write(0, 0xE001);
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        mmc3.cpu_write(0, 0xE000);
+        assert!(!mmc3.irq());
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq());
+
+        mmc3.cpu_write(0, 0xE001);
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn short_drops_of_a12_are_filtered_out() {
+        let mut mmc3 = mmc3();
+        mmc3.cpu_write(0, 0xC000);
+        mmc3.cpu_write(0, 0xE001);
+
+        // A12 going low for fewer fetches than the filter doesn't count as a new rise
+        mmc3.watch_ppu_address(0x1000);
+        for _ in 0..A12_FILTER - 1 {
+            mmc3.watch_ppu_address(0x0000);
+        }
+        mmc3.watch_ppu_address(0x1000);
+        mmc3.watch_ppu_address(0x1FFF);
+        assert!(!mmc3.irq());
+
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+}
@@ -0,0 +1,56 @@
+use mapper::{ChrMemory, Mapper, Mirroring, PrgRam};
+
+// Mapper 0. 16k or 32k of PRG-ROM, 8k of CHR-ROM (or RAM) and no bank switching at all.
+pub struct NROM {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+    // Only a few boards (Family BASIC) actually have this
+    prgram: PrgRam,
+    mirroring: Mirroring,
+}
+
+impl NROM {
+    pub fn new(rominfo: ::ines::INesInfo) -> NROM {
+        NROM {prgrom: rominfo.prgrom, chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size), prgram: PrgRam::new(rominfo.prg_ram_size + rominfo.prg_nvram_size), mirroring: rominfo.mirroring}
+    }
+}
+
+impl Mapper for NROM {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            0x6000 ... 0x7FFF => self.prgram.read(address as usize - 0x6000),
+            // NROM-128 mirrors its 16k at $C000, NROM-256 fills the whole window
+            0x8000 ... 0xFFFF => self.prgrom[(address as usize - 0x8000) % self.prgrom.len()],
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        match address {
+            0x6000 ... 0x7FFF => self.prgram.write(data, address as usize - 0x6000),
+            // There are no registers, writes to ROM are ignored
+            0x8000 ... 0xFFFF => {},
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        self.chr.write(data, address as usize);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prgram.data()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prgram.data_mut()
+    }
+}
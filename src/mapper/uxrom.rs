@@ -0,0 +1,76 @@
+use mapper::{ChrMemory, Mapper, Mirroring};
+
+// Mapper 2. A switchable 16k PRG bank at $8000 with the last bank fixed at $C000.
+pub struct UxROM {
+    prgrom: Vec<u8>,
+    chr: ChrMemory,
+    mirroring: Mirroring,
+
+    prg_bank: u8,
+}
+
+impl UxROM {
+    pub fn new(rominfo: ::ines::INesInfo) -> UxROM {
+        UxROM {prgrom: rominfo.prgrom, chr: ChrMemory::new(rominfo.chrrom, rominfo.chr_ram_size + rominfo.chr_nvram_size), mirroring: rominfo.mirroring, prg_bank: 0}
+    }
+}
+
+impl Mapper for UxROM {
+    fn cpu_read(&self, address: u16) -> u8 {
+        let prg_banks = self.prgrom.len() / 0x4000;
+        match address {
+            0x8000 ... 0xBFFF => {
+                let bank = self.prg_bank as usize % prg_banks;
+                self.prgrom[bank * 0x4000 + (address as usize - 0x8000)]
+            }
+            0xC000 ... 0xFFFF => {
+                let bank = prg_banks - 1;
+                self.prgrom[bank * 0x4000 + (address as usize - 0xC000)]
+            }
+            _ => 0
+        }
+    }
+
+    fn cpu_write(&mut self, data: u8, address: u16) {
+        if address >= 0x8000 {
+            self.prg_bank = data;
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, data: u8, address: u16) {
+        self.chr.write(data, address as usize);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapper::tests::rominfo;
+
+    #[test]
+    fn switches_8000_and_fixes_the_last_bank_at_c000() {
+        // 8 banks of 16k
+        let mut uxrom = UxROM::new(rominfo(2, 0, 0x20000, 0));
+        assert_eq!(uxrom.cpu_read(0x8000), 0);
+        assert_eq!(uxrom.cpu_read(0xC000), 14);
+
+        uxrom.cpu_write(3, 0x8000);
+        assert_eq!(uxrom.cpu_read(0x8000), 6);
+        assert_eq!(uxrom.cpu_read(0xBFFF), 7);
+        assert_eq!(uxrom.cpu_read(0xC000), 14);
+        assert_eq!(uxrom.cpu_read(0xFFFF), 15);
+
+        // Bank numbers past the end of the ROM wrap around
+        uxrom.cpu_write(9, 0xFFFF);
+        assert_eq!(uxrom.cpu_read(0x8000), 2);
+        assert_eq!(uxrom.cpu_read(0xC000), 14);
+    }
+}
@@ -1,29 +1,41 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use bus::{Bus, NesBus};
+use debugger::{Access, Space, Watches};
+
 pub struct CPUMemoryMap {
     pub ppu: ::ppu::PPU,
+    pub apu: RefCell<::apu::APU>,
     pub ram: Box<[u8]>,
     // input
     pub cart: Rc<RefCell<::cartridge::Cartridge>>,
     pub controller1: RefCell<::controller::Controller>,
     pub controller2: RefCell<::controller::Controller>,
+    // Page written to $4014, the CPU stalls to copy it into OAM after the write
+    pub dma_page: Option<u8>,
+    // Debugger watchpoints, checked on every access the CPU makes
+    pub watches: Option<Rc<RefCell<Watches>>>,
 }
 
 pub struct PPUMemoryMap {
     pub vram: Box<[u8]>,
     pub cart: Rc<RefCell<::cartridge::Cartridge>>,
-    pub palettes: Box<[u8]>
+    pub palettes: Box<[u8]>,
+    // Debugger watchpoints, checked on every access the PPU makes
+    pub watches: Option<Rc<RefCell<Watches>>>,
 }
 
-// For now I'm just gonna assume NROM-128 because I'm just focusing on Donkey Kong
 impl CPUMemoryMap {
-    pub fn new(cart: Rc<RefCell<::cartridge::Cartridge>>, ppu: ::ppu::PPU) -> CPUMemoryMap {
+    pub fn new(cart: Rc<RefCell<::cartridge::Cartridge>>, ppu: ::ppu::PPU, apu: ::apu::APU) -> CPUMemoryMap {
         CPUMemoryMap{ram: Box::new([0; 0x800]),
                      cart: cart,
                      ppu: ppu,
+                     apu: RefCell::new(apu),
                      controller1: RefCell::new(::controller::Controller::new(true)),
                      controller2: RefCell::new(::controller::Controller::new(false)),
+                     dma_page: None,
+                     watches: None,
                     }
     }
 
@@ -53,22 +65,11 @@ impl CPUMemoryMap {
                 }
             },
 
-            0x4000 => { panic!("Read from 0x4000") },
-            0x4001 => { panic!("Read from 0x4001") },
-            0x4002 => { panic!("Read from 0x4002") },
-            0x4003 => { panic!("Read from 0x4003") },
-            0x4004 => { panic!("Read from 0x4004") },
-            0x4005 => { panic!("Read from 0x4005") },
-            0x4006 => { panic!("Read from 0x4006") },
-            0x4007 => { panic!("Read from 0x4007") },
-            0x4008 => { panic!("Read from 0x4008") },
-            0x4009 => { panic!("Read from 0x4009") },
-            0x4010 => { panic!("Read from 0x4010") },
-            0x4011 => { panic!("Read from 0x4011") },
-            0x4012 => { panic!("Read from 0x4012") },
-            0x4013 => { panic!("Read from 0x4013") },
-            0x4014 => { panic!("Read from 0x4014") },
-            0x4015 => { panic!("Read from 0x4015") },
+            // APU registers are write only except for the status register
+            0x4000 ... 0x4014 => 0,
+            0x4015 => {
+                self.apu.borrow_mut().read_status()
+            },
 
             // Controller ports
             0x4016 => {
@@ -79,37 +80,73 @@ impl CPUMemoryMap {
             },
 
 
-            0x4018 ... 0x401F => {
-                panic!("0x4018 ... 0x401F");
-            },
-
-            // PRG-RAM
-            0x6000 ... 0x7FFF => {
-                panic!("PRG-RAM");
-            },
+            // CPU test mode registers, disabled on a retail NES
+            0x4018 ... 0x401F => 0,
 
-            // First 16k of ROM
-            0x8000 ... 0xBFFF => {
-                let cart = self.cart.borrow();
-                cart.prgrom[address as usize - 0x8000]
-            }
-
-            // Last 16k of ROM (just a mirror for NROM-128)
-            0xC000 ... 0xFFFF => {
-                // REMEMBER THIS IS A MIRROR OF THE PREVIOUS BECAUSE NROM 128
-                let cart = self.cart.borrow();
-                cart.prgrom[address as usize - 0xC000]
+            // PRG-RAM and PRG-ROM, banked by the cartridge's mapper
+            0x6000 ... 0xFFFF => {
+                self.cart.borrow().read(address)
             }
             _ => 0
         }
     }
 
+    // Runs the rest of the system for one CPU cycle
+    pub fn tick(&mut self) {
+        self.ppu.step(3);
+        self.apu.borrow_mut().step(1);
+    }
+
+    // The CPU's IRQ line, held by the APU and the cartridge's mapper
+    pub fn irq(&self) -> bool {
+        self.apu.borrow().irq() || self.cart.borrow().irq()
+    }
+
+    // Where the PPU is as (frame, scanline, dot)
+    pub fn video_position(&self) -> (u64, u16, u16) {
+        let (scanline, dot) = self.ppu.position();
+        (self.ppu.frame(), scanline, dot)
+    }
+
     pub fn read16(&self, address: u16) -> u16 {
             let lo = self.read(address) as u16;
-            let hi = self.read(address + 1) as u16;
+            let hi = self.read(address.wrapping_add(1)) as u16;
             hi << 8 | lo
     }
 
+    // Reads without side effects, for debugging output. Reading the PPU, APU or controller
+    // registers changes their state so they show up as $FF like they do in Nintendulator.
+    pub fn peek(&self, address: u16) -> u8 {
+        match address {
+            0x2000 ... 0x401F => 0xFF,
+            _ => self.read(address),
+        }
+    }
+
+    // Whether poke can write to address
+    pub fn pokeable(address: u16) -> bool {
+        match address {
+            0 ... 0x1fff | 0x6000 ... 0x7fff => true,
+            _ => false
+        }
+    }
+
+    // Writes for debugging. Only RAM and PRG-RAM can be written, since writes anywhere else have
+    // side effects. Returns whether the write happened.
+    pub fn poke(&mut self, data: u8, address: u16) -> bool {
+        match address {
+            0 ... 0x1fff => {
+                self.ram[address as usize % 0x800] = data;
+                true
+            },
+            0x6000 ... 0x7fff => {
+                self.cart.borrow_mut().write(data, address);
+                true
+            },
+            _ => false
+        }
+    }
+
     pub fn write(&mut self, data: u8, address: u16) {
         match address {
             // 2k of ram repeated 4 times
@@ -135,33 +172,30 @@ impl CPUMemoryMap {
                 }
             },
 
-            0x4000 => {},
-            0x4001 => {},
-            0x4002 => {},
-            0x4003 => {},
-            0x4004 => {},
-            0x4005 => {},
-            0x4006 => {},
-            0x4007 => {},
-            0x4008 => {},
+            0x4000 => self.apu.borrow_mut().pulse1.write_control(data),
+            0x4001 => self.apu.borrow_mut().pulse1.write_sweep(data),
+            0x4002 => self.apu.borrow_mut().pulse1.write_timer_low(data),
+            0x4003 => self.apu.borrow_mut().pulse1.write_timer_high(data),
+            0x4004 => self.apu.borrow_mut().pulse2.write_control(data),
+            0x4005 => self.apu.borrow_mut().pulse2.write_sweep(data),
+            0x4006 => self.apu.borrow_mut().pulse2.write_timer_low(data),
+            0x4007 => self.apu.borrow_mut().pulse2.write_timer_high(data),
+            0x4008 => self.apu.borrow_mut().triangle.write_control(data),
             0x4009 => {},
-            0x400a => {},
-            0x400b => {},
-            0x400c => {},
+            0x400a => self.apu.borrow_mut().triangle.write_timer_low(data),
+            0x400b => self.apu.borrow_mut().triangle.write_timer_high(data),
+            0x400c => self.apu.borrow_mut().noise.write_control(data),
             0x400d => {},
-            0x400e => {},
-            0x400f => {},
-            0x4010 => {},
-            0x4011 => {},
-            0x4012 => {},
-            0x4013 => {},
+            0x400e => self.apu.borrow_mut().noise.write_period(data),
+            0x400f => self.apu.borrow_mut().noise.write_length(data),
+            0x4010 => self.apu.borrow_mut().dmc.write_control(data),
+            0x4011 => self.apu.borrow_mut().dmc.write_value(data),
+            0x4012 => self.apu.borrow_mut().dmc.write_address(data),
+            0x4013 => self.apu.borrow_mut().dmc.write_length(data),
             0x4014 => {
-                let addr:u16 = ((data as u16) << 8);
-                for i in 0..256 {
-                    self.ppu.oam[i] = self.read(addr + (i as u16));
-                }
+                self.dma_page = Some(data);
             },
-            0x4015 => {},
+            0x4015 => self.apu.borrow_mut().write_control(data),
 
             // Controller ports
             0x4016 => {
@@ -173,48 +207,119 @@ impl CPUMemoryMap {
                     self.controller2.borrow_mut().strobe = false;
                 }
             },
+            // Writes go to the APU frame counter, not the second controller
             0x4017 => {
-                self.controller2.borrow_mut().write(data)
-            },
-
-            0x4018 ... 0x401F => {
-                panic!("0x4018 ... 0x401F");
+                self.apu.borrow_mut().write_frame_counter(data)
             },
 
-            // PRG-RAM
-            0x6000 ... 0x7FFF => {
-                panic!("PRG-RAM");
-            },
+            // CPU test mode registers, disabled on a retail NES
+            0x4018 ... 0x401F => {},
 
-            // First 16k of ROM
-            0x8000 ... 0xBFFF => {
-                panic!("Attempt to write to ROM");
+            // PRG-RAM, and writes to ROM are how most mappers switch banks
+            0x6000 ... 0xFFFF => {
+                self.cart.borrow_mut().write(data, address);
             }
 
-            // Last 16k of ROM (just a mirror for NROM-128)
-            0xC000 ... 0xFFFF => {
-                // REMEMBER THIS IS A MIRROR OF THE PREVIOUS BECAUSE NROM 128
-                panic!("Attempt to write to ROM");
-            }
-            _ => panic!("Write to an address outside of CPU memory range")
+            // Expansion area, nothing on the boards we support listens here
+            0x4020 ... 0x5FFF => {}
         }
     }
 }
 
+impl Bus for CPUMemoryMap {
+    fn read(&mut self, address: u16) -> u8 {
+        let data = CPUMemoryMap::read(self, address);
+        if let Some(ref watches) = self.watches {
+            watches.borrow_mut().check(Space::Cpu, Access::Read, address, data);
+        }
+        data
+    }
+
+    fn write(&mut self, data: u8, address: u16) {
+        if let Some(ref watches) = self.watches {
+            watches.borrow_mut().check(Space::Cpu, Access::Write, address, data);
+        }
+        CPUMemoryMap::write(self, data, address)
+    }
+
+    fn tick(&mut self) {
+        CPUMemoryMap::tick(self)
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        CPUMemoryMap::peek(self, address)
+    }
+
+    fn irq(&self) -> bool {
+        CPUMemoryMap::irq(self)
+    }
+}
+
+impl NesBus for CPUMemoryMap {
+    // Reset silences the APU
+    fn reset(&mut self) {
+        self.apu.borrow_mut().write_control(0);
+    }
+
+    fn take_dma_page(&mut self) -> Option<u8> {
+        self.dma_page.take()
+    }
+
+    fn video_position(&self) -> (u64, u16, u16) {
+        CPUMemoryMap::video_position(self)
+    }
+}
+
 impl PPUMemoryMap {
     pub fn new(cart: Rc<RefCell<::cartridge::Cartridge>>) -> PPUMemoryMap {
-        PPUMemoryMap{vram: Box::new([0xFF; 0x800]) , cart: cart, palettes: Box::new([0; 0x800])}
+        PPUMemoryMap{vram: Box::new([0xFF; 0x800]) , cart: cart, palettes: Box::new([0; 0x800]), watches: None}
+    }
+
+    // Map a nametable address onto the 4k of physical nametable memory according to the
+    // cartridge's mirroring. The first 2k is our vram, anything past that lives on the cartridge.
+    fn nametable_address(&self, address: u16) -> usize {
+        // $3000 - $3EFF mirrors $2000 - $2EFF
+        let table = ((address as usize - 0x2000) / 0x400) % 4;
+        let offset = address as usize % 0x400;
+
+        let physical = match self.cart.borrow().mirroring() {
+            ::mapper::Mirroring::Horizontal => table / 2,
+            ::mapper::Mirroring::Vertical => table % 2,
+            ::mapper::Mirroring::SingleScreenLower => 0,
+            ::mapper::Mirroring::SingleScreenUpper => 1,
+            ::mapper::Mirroring::FourScreen => table,
+        };
+
+        physical * 0x400 + offset
     }
 
     pub fn read(&self, address: u16) -> u8 {
+        // Mappers like the MMC3 watch the address bus, palettes are internal to the PPU
+        if address < 0x3F00 {
+            self.cart.borrow_mut().watch_ppu_address(address);
+        }
+
+        let data = self.peek(address);
+        if let Some(ref watches) = self.watches {
+            watches.borrow_mut().check(Space::Ppu, Access::Read, address, data);
+        }
+        data
+    }
+
+    // Reads without side effects, for debugging
+    pub fn peek(&self, address: u16) -> u8 {
         match address {
             0 ... 0x1FFF => {
-                let cart = self.cart.borrow();
-                cart.chrrom[(address) as usize]
+                self.cart.borrow().ppu_read(address)
             },
 
             0x2000 ... 0x3EFF => {
-                self.vram[address as usize % 0x800]
+                let address = self.nametable_address(address);
+                if address < 0x800 {
+                    self.vram[address]
+                } else {
+                    self.cart.borrow().vram[address - 0x800]
+                }
             }
 
             0x3F00 ... 0x3FFF => {
@@ -225,13 +330,22 @@ impl PPUMemoryMap {
     }
 
     pub fn write(&mut self, data: u8, address: u16) {
+        if let Some(ref watches) = self.watches {
+            watches.borrow_mut().check(Space::Ppu, Access::Write, address, data);
+        }
+
         match address {
             0 ... 0x1FFF => {
-                panic!("Attempt to write to ROM");
+                self.cart.borrow_mut().ppu_write(data, address);
             },
 
             0x2000 ... 0x3EFF => {
-                self.vram[address as usize % 0x800] = data;
+                let address = self.nametable_address(address);
+                if address < 0x800 {
+                    self.vram[address] = data;
+                } else {
+                    self.cart.borrow_mut().vram[address - 0x800] = data;
+                }
             }
 
             0x3F00 ... 0x3FFF => {
@@ -19,6 +19,9 @@ pub struct PPU {
 
     oddframe: bool,
 
+    // Frames rendered since power on
+    frame: u64,
+
     write_toggle: bool,
 
     memory: ::memory::PPUMemoryMap,
@@ -107,6 +110,7 @@ impl PPU {
             temp_addr: 0,
             x: 0,
             oddframe:false,
+            frame: 0,
 
             write_toggle: false,
             nametablebyte: 0,
@@ -161,6 +165,25 @@ impl PPU {
             (data & 0x0F) as u8
     }
 
+    // The current scanline and the cycle within it
+    pub fn position(&self) -> (u16, u16) {
+        (self.scanline, self.cycle)
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Reads PPU memory without side effects, for debugging
+    pub fn peek_memory(&self, address: u16) -> u8 {
+        self.memory.peek(address)
+    }
+
+    // Debugger watchpoints to check on PPU memory accesses
+    pub fn watch(&mut self, watches: Option<Rc<RefCell<::debugger::Watches>>>) {
+        self.memory.watches = watches;
+    }
+
     pub fn step(&mut self, cycles: u8) {
         for i in 0..cycles {
             self.cycle();
@@ -216,6 +239,7 @@ impl PPU {
                 self.cycle = 0;
                 self.scanline = 0;
                 self.oddframe = false;
+                self.frame += 1;
                 return;
             }
         }
@@ -228,6 +252,7 @@ impl PPU {
             if self.scanline > 261 {
                 self.scanline = 0;
                 self.oddframe = if self.oddframe { false } else { true };
+                self.frame += 1;
             }
         }
     }
@@ -297,6 +322,24 @@ impl PPU {
         }
 
         self.sprite_count = count as u8;
+        self.fetch_unused_sprites(count);
+    }
+
+    // The PPU fetches patterns for all 8 sprite slots even when fewer sprites are on the line,
+    // using tile $FF for the empty ones. Mappers that watch A12 count scanlines with these.
+    fn fetch_unused_sprites(&self, count: usize) {
+        // 8x16 sprites take the pattern table from bit 0 of the tile instead, so tile $FF is
+        // fetched from $1000 whatever $2000 says
+        let address = if self.flag_sprite_size {
+            0x1000 + 0xFE * 16
+        } else {
+            let table = if self.flag_sprite_table_address { 0x1000 } else { 0x0 };
+            table + 0xFF * 16
+        };
+        for _ in count..8 {
+            self.memory.read(address);
+            self.memory.read(address + 8);
+        }
     }
 
     fn get_sprite_pixel(&self) -> (u8, u8) {
@@ -467,6 +510,9 @@ impl PPU {
                         self.evaluate_sprites();
                     } else {
                         self.sprite_count = 0;
+                        if pre_line {
+                            self.fetch_unused_sprites(0);
+                        }
                     }
                 }
             }
@@ -537,7 +583,8 @@ impl PPU {
     }
 
     pub fn write_oamdata(&mut self, data: u8) {
-        panic!("Oamdata writing not yet implemented");
+        self.oam[self.oamaddr as usize] = data;
+        self.oamaddr = self.oamaddr.wrapping_add(1);
     }
 
     pub fn write_scroll_offset(&mut self, data: u8) {
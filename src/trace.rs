@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+// Where CPU::step logs a line for every instruction it runs, in the format of CPU::trace. The
+// filters keep logs of long runs down to the part you care about, an instruction is logged when
+// its address and the current frame fall within both.
+pub struct Tracer {
+    out: Box<dyn Write>,
+
+    // Tracing can be paused without closing the log
+    pub enabled: bool,
+
+    pub pcs: Option<RangeInclusive<u16>>,
+    pub frames: Option<RangeInclusive<u64>>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(out: W) -> Tracer {
+        Tracer {
+            out: Box::new(out),
+            enabled: true,
+            pcs: None,
+            frames: None,
+        }
+    }
+
+    // Logs to a new file at path, replacing anything already there
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Tracer> {
+        Ok(Tracer::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn wants(&self, pc: u16, frame: u64) -> bool {
+        self.enabled &&
+            self.pcs.as_ref().map_or(true, |pcs| pcs.contains(&pc)) &&
+            self.frames.as_ref().map_or(true, |frames| frames.contains(&frame))
+    }
+
+    pub fn log(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.out, "{}", line)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
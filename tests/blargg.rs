@@ -0,0 +1,53 @@
+// Runs every blargg test ROM found under tests/roms/blargg, or the directory in $BLARGG_ROMS.
+// The ROMs aren't distributed with the emulator so this is ignored, run it with cargo test -- --ignored.
+
+extern crate nes;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Long enough for the slowest of the suites, which take around 20 seconds
+const TIMEOUT_FRAMES: u64 = 60 * 60;
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if path.extension().map_or(false, |ext| ext == "nes") {
+            roms.push(path);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs blargg's test ROMs in tests/roms/blargg or BLARGG_ROMS"]
+fn blargg_test_roms() {
+    let dir = match env::var_os("BLARGG_ROMS") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join("blargg"),
+    };
+
+    let mut roms = Vec::new();
+    find_roms(&dir, &mut roms);
+    roms.sort();
+
+    assert!(!roms.is_empty(), "no test ROMs found in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for rom in &roms {
+        match nes::blargg::run(rom, TIMEOUT_FRAMES) {
+            Ok(ref result) if result.passed() => println!("passed {}", rom.display()),
+            Ok(result) => failures.push(format!("{}: failed with status {}\n{}", rom.display(), result.status, result.message)),
+            Err(err) => failures.push(format!("{}: {}", rom.display(), err)),
+        }
+    }
+
+    assert!(failures.is_empty(), "{} of {} test ROMs failed\n\n{}", failures.len(), roms.len(), failures.join("\n\n"));
+}
@@ -0,0 +1,57 @@
+// Cartridges and memory maps shared by the tests that need the whole NES around the CPU. Tests
+// that only need a 6502 run it on a FlatRam instead.
+
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::rc::Rc;
+
+use nes::apu::APU;
+use nes::cartridge::Cartridge;
+use nes::cpu::CPU;
+use nes::ines::INesInfo;
+use nes::memory::CPUMemoryMap;
+use nes::ppu::PPU;
+
+pub fn read_file(path: &Path) -> Vec<u8> {
+    let mut data = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
+
+pub fn load(rom: &[u8]) -> Rc<RefCell<Cartridge>> {
+    let rominfo = INesInfo::from_bytes(rom).unwrap();
+    Rc::new(RefCell::new(Cartridge::new(rominfo).unwrap()))
+}
+
+// nestest.nes as it comes
+pub fn nestest() -> Rc<RefCell<Cartridge>> {
+    load(&read_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join("nestest.nes")))
+}
+
+// nestest.nes with the reset vector pointed at $C000, so it runs its automation mode and doesn't
+// need the PPU for output. It starts C000 JMP $C5F5 ... C5FD JSR $C72D.
+pub fn nestest_automation() -> CPU {
+    let mut rom = read_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join("nestest.nes"));
+    rom[16 + 0x3FFC] = 0x00;
+    rom[16 + 0x3FFD] = 0xC0;
+    CPU::new(memory_map(load(&rom)))
+}
+
+// A cartridge with 32k of PRG-ROM and 8k of CHR-ROM on the given mapper, flags 6 picks the
+// mirroring
+pub fn cartridge(mapper: u8, flags6: u8) -> Rc<RefCell<Cartridge>> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, (mapper << 4) | flags6, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    rom.extend(vec![0; 0x8000 + 0x2000]);
+    load(&rom)
+}
+
+// The CPU's view of the NES with cartridge plugged in
+pub fn memory_map(cartridge: Rc<RefCell<Cartridge>>) -> CPUMemoryMap {
+    let ppu = PPU::new(cartridge.clone());
+    let apu = APU::new(cartridge.clone());
+    CPUMemoryMap::new(cartridge, ppu, apu)
+}
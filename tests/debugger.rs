@@ -0,0 +1,183 @@
+// Drives the debugger over nestest.nes in its automation mode, which starts
+//   C5F5  LDX #$00
+//   C5F7  STX $00
+//   ...
+//   C5FD  JSR $C72D
+//   C600  JSR $C7DB
+
+extern crate nes;
+
+mod common;
+
+use nes::bus::Bus;
+use nes::cpu::{CPU, Interrupt};
+use nes::debugger::{Access, Debugger, Space, Stop, WatchHit, Watchpoint};
+use nes::debugger::repl::Repl;
+use nes::disasm::disassemble;
+
+fn debug() -> (Debugger, CPU) {
+    let mut cpu = common::nestest_automation();
+    let debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+    (debugger, cpu)
+}
+
+fn run(debugger: &mut Debugger, cpu: &mut CPU) -> Stop {
+    for _ in 0..100000 {
+        if let Some(stop) = debugger.check(cpu) {
+            return stop;
+        }
+        cpu.step(Interrupt::IntNone);
+    }
+
+    panic!("the debugger never stopped");
+}
+
+// Like run() but delivers the PPU's NMIs the way the emulator's main loop does
+fn run_with_nmi(debugger: &mut Debugger, cpu: &mut CPU) -> Stop {
+    for _ in 0..100000 {
+        if let Some(stop) = debugger.check(cpu) {
+            return stop;
+        }
+        let int = if cpu.memory.ppu.nmi { cpu.memory.ppu.nmi = false; Interrupt::IntNMI } else { Interrupt::IntNone };
+        cpu.step(int);
+    }
+
+    panic!("the debugger never stopped");
+}
+
+#[test]
+fn stops_at_breakpoints() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.breakpoints.push(0xC5FD);
+
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Breakpoint);
+    assert_eq!(cpu.state().pc, 0xC5FD);
+    assert!(!debugger.is_running());
+}
+
+#[test]
+fn stops_at_breakpoints_on_the_nmi_handler() {
+    let (mut debugger, mut cpu) = debug();
+    let handler = cpu.memory.peek(0xFFFA) as u16 | (cpu.memory.peek(0xFFFB) as u16) << 8;
+    debugger.breakpoints.push(handler);
+
+    // Turn on the NMI at vblank
+    Bus::write(&mut cpu.memory, 0x80, 0x2000);
+
+    assert_eq!(run_with_nmi(&mut debugger, &mut cpu), Stop::Breakpoint);
+    assert_eq!(cpu.state().pc, handler);
+
+    // Stepping runs the handler's first instruction rather than skipping over it
+    debugger.step_into(1);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(run_with_nmi(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.state().pc, handler + disassemble(&cpu.memory, handler).len());
+}
+
+#[test]
+fn steps_into_over_and_out_of_subroutines() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.breakpoints.push(0xC5FD);
+    run(&mut debugger, &mut cpu);
+    let sp = cpu.state().sp;
+
+    debugger.step_into(1);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.state().pc, 0xC72D);
+
+    debugger.step_out(&cpu);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.state().pc, 0xC600);
+    assert_eq!(cpu.state().sp, sp);
+
+    debugger.step_over(&cpu);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.state().pc, 0xC603);
+    assert_eq!(cpu.state().sp, sp);
+}
+
+#[test]
+fn steps_a_number_of_instructions() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.breakpoints.push(0xC5F5);
+    run(&mut debugger, &mut cpu);
+
+    debugger.step_into(4);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.state().pc, 0xC5FD);
+}
+
+#[test]
+fn stops_after_cpu_watchpoints() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.add_watchpoint(Watchpoint { space: Space::Cpu, start: 0x0000, end: 0x0000, read: false, write: true });
+
+    let hit = WatchHit { space: Space::Cpu, access: Access::Write, address: 0x0000, data: 0x00 };
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Watchpoint(hit));
+    assert_eq!(cpu.state().pc, 0xC5F9);
+
+    assert!(debugger.remove_watchpoint(0).is_some());
+    assert!(debugger.watchpoints().is_empty());
+}
+
+#[test]
+fn stops_after_ppu_watchpoints() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.add_watchpoint(Watchpoint { space: Space::Ppu, start: 0x3F00, end: 0x3F1F, read: false, write: true });
+
+    // Point PPUADDR at the palette and write to PPUDATA
+    Bus::write(&mut cpu.memory, 0x3F, 0x2006);
+    Bus::write(&mut cpu.memory, 0x01, 0x2006);
+    Bus::write(&mut cpu.memory, 0x2A, 0x2007);
+
+    let hit = WatchHit { space: Space::Ppu, access: Access::Write, address: 0x3F01, data: 0x2A };
+    assert_eq!(debugger.check(&cpu), Some(Stop::Watchpoint(hit)));
+    assert_eq!(cpu.memory.ppu.peek_memory(0x3F01), 0x2A);
+}
+
+#[test]
+fn runs_to_a_scanline() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.run_to_scanline(100);
+
+    assert_eq!(run(&mut debugger, &mut cpu), Stop::Scanline);
+    let (_, scanline, _) = cpu.memory.video_position();
+    assert_eq!(scanline, 100);
+}
+
+#[test]
+fn repl_commands() {
+    let (mut debugger, mut cpu) = debug();
+    debugger.pause();
+    let stop = run(&mut debugger, &mut cpu);
+
+    let input = b"b C5FD\nw $0010-0011 w\nx 0 2\nc\n";
+    let mut output = Vec::new();
+    let keep_running = Repl::new(&input[..], &mut output).prompt(&mut debugger, &mut cpu, stop).unwrap();
+    assert!(keep_running);
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("C000  4C F5 C5  JMP $C5F5"));
+    assert!(output.contains("0000: 00 00\n"));
+    assert_eq!(debugger.breakpoints, vec![0xC5FD]);
+    assert_eq!(debugger.watchpoints()[0], Watchpoint { space: Space::Cpu, start: 0x10, end: 0x11, read: false, write: true });
+
+    cpu.step(Interrupt::IntNone);
+    let stop = run(&mut debugger, &mut cpu);
+    assert_eq!(stop, Stop::Watchpoint(WatchHit { space: Space::Cpu, access: Access::Write, address: 0x10, data: 0 }));
+
+    let input = b"regs\nbogus\nq\n";
+    let mut output = Vec::new();
+    let keep_running = Repl::new(&input[..], &mut output).prompt(&mut debugger, &mut cpu, stop).unwrap();
+    assert!(!keep_running);
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("Watchpoint: CPU write of $00 at $0010\n"));
+    assert!(output.contains("PC:C5FB A:00 X:00 Y:00 SP:FD P:26 nv-bdIZc\n"));
+    assert!(output.contains("unknown command"));
+}
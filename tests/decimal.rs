@@ -0,0 +1,118 @@
+// Checks ADC and SBC in decimal mode against every pair of valid BCD operands, on the NMOS 6502
+// variant where it's honoured and on the 2A03 where the D flag is ignored
+
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant};
+
+const ADC_ZERO_PAGE: u8 = 0x65;
+const SBC_ZERO_PAGE: u8 = 0xE5;
+
+// Inputs and outputs live in the zero page
+const FLAGS_IN: usize = 0;
+const A_IN: usize = 1;
+const OPERAND: usize = 2;
+const A_OUT: usize = 3;
+const FLAGS_OUT: usize = 4;
+
+// Loads the flags and A from the zero page, does the arithmetic and stores A and the flags
+fn program(opcode: u8) -> Vec<u8> {
+    vec![
+        0xA5, FLAGS_IN as u8,   // LDA FLAGS_IN
+        0x48,                   // PHA
+        0x28,                   // PLP
+        0xA5, A_IN as u8,       // LDA A_IN
+        opcode, OPERAND as u8,  // ADC/SBC OPERAND
+        0x85, A_OUT as u8,      // STA A_OUT
+        0x08,                   // PHP
+        0x68,                   // PLA
+        0x85, FLAGS_OUT as u8,  // STA FLAGS_OUT
+        0x4C, 0x00, 0x02,       // JMP $0200
+    ]
+}
+const PROGRAM_INSTRUCTIONS: usize = 10;
+
+// The program at $0200 on plain RAM
+fn cpu(opcode: u8, variant: Variant) -> CPU<FlatRam> {
+    let mut ram = FlatRam::new();
+    ram.load(&program(opcode), 0x0200);
+    ram.load(&[0x00, 0x02], 0xFFFC);
+    CPU::with_variant(ram, variant)
+}
+
+// Runs the program once, returning A and the flags
+fn run(cpu: &mut CPU<FlatRam>, a: u8, operand: u8, flags: u8) -> (u8, u8) {
+    cpu.memory.data[FLAGS_IN] = flags;
+    cpu.memory.data[A_IN] = a;
+    cpu.memory.data[OPERAND] = operand;
+
+    for _ in 0..PROGRAM_INSTRUCTIONS {
+        cpu.step(Interrupt::IntNone);
+    }
+
+    (cpu.memory.data[A_OUT], cpu.memory.data[FLAGS_OUT])
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+const CARRY: u8 = 0x01;
+const ZERO: u8 = 0x02;
+const DECIMAL: u8 = 0x08;
+
+#[test]
+fn nmos_adc_decimal() {
+    let mut cpu = cpu(ADC_ZERO_PAGE, Variant::Nmos6502);
+
+    for a in 0..100u8 {
+        for m in 0..100u8 {
+            for carry in 0..2u8 {
+                let (res, flags) = run(&mut cpu, to_bcd(a), to_bcd(m), DECIMAL | carry);
+                let sum = a as u16 + m as u16 + carry as u16;
+
+                assert_eq!(res, to_bcd((sum % 100) as u8), "{} + {} + {}", a, m, carry);
+                assert_eq!(flags & CARRY != 0, sum > 99, "carry from {} + {} + {}", a, m, carry);
+            }
+        }
+    }
+}
+
+#[test]
+fn nmos_sbc_decimal() {
+    let mut cpu = cpu(SBC_ZERO_PAGE, Variant::Nmos6502);
+
+    for a in 0..100u8 {
+        for m in 0..100u8 {
+            for carry in 0..2u8 {
+                let (res, flags) = run(&mut cpu, to_bcd(a), to_bcd(m), DECIMAL | carry);
+                let difference = a as i16 - m as i16 - (1 - carry as i16);
+
+                assert_eq!(res, to_bcd(((difference + 100) % 100) as u8), "{} - {} - {}", a, m, 1 - carry);
+                assert_eq!(flags & CARRY != 0, difference >= 0, "borrow from {} - {} - {}", a, m, 1 - carry);
+
+                // Z comes from the binary result on the NMOS 6502
+                let binary = to_bcd(a).wrapping_sub(to_bcd(m)).wrapping_sub(1 - carry);
+                assert_eq!(flags & ZERO != 0, binary == 0, "zero from {} - {} - {}", a, m, 1 - carry);
+            }
+        }
+    }
+}
+
+#[test]
+fn ricoh_2a03_ignores_decimal_flag() {
+    let mut adc = cpu(ADC_ZERO_PAGE, Variant::Ricoh2A03);
+    let mut sbc = cpu(SBC_ZERO_PAGE, Variant::Ricoh2A03);
+
+    for a in 0..=255u8 {
+        for &m in &[0x00, 0x01, 0x09, 0x10, 0x45, 0x99, 0xFF] {
+            let (res, flags) = run(&mut adc, a, m, DECIMAL);
+            assert_eq!(res, a.wrapping_add(m));
+            assert_eq!(flags & DECIMAL, DECIMAL);
+
+            let (res, _) = run(&mut sbc, a, m, DECIMAL | CARRY);
+            assert_eq!(res, a.wrapping_sub(m));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::disasm::disassemble;
+
+fn ram(program: &[u8], address: u16) -> FlatRam {
+    let mut ram = FlatRam::new();
+    ram.load(program, address);
+    ram
+}
+
+#[test]
+fn formats_every_addressing_mode() {
+    let cases: &[(&[u8], &str)] = &[
+        (&[0xEA], "NOP"),
+        (&[0x0A], "ASL A"),
+        (&[0xA9, 0x3F], "LDA #$3F"),
+        (&[0xA5, 0x10], "LDA $10"),
+        (&[0xB5, 0x10], "LDA $10,X"),
+        (&[0xB6, 0x10], "LDX $10,Y"),
+        (&[0xD0, 0xFE], "BNE $0200"),
+        (&[0xAD, 0x00, 0x02], "LDA $0200"),
+        (&[0xBD, 0x00, 0x02], "LDA $0200,X"),
+        (&[0xB9, 0x00, 0x02], "LDA $0200,Y"),
+        (&[0x6C, 0xFC, 0xFF], "JMP ($FFFC)"),
+        (&[0xA1, 0x10], "LDA ($10,X)"),
+        (&[0xB1, 0x10], "LDA ($10),Y"),
+    ];
+
+    for &(program, text) in cases {
+        let instruction = disassemble(&ram(program, 0x0200), 0x0200);
+        assert_eq!(instruction.to_string(), text);
+        assert_eq!(instruction.len(), program.len() as u16);
+        assert_eq!(instruction.bytes, program);
+    }
+}
+
+#[test]
+fn flags_unofficial_opcodes() {
+    assert!(disassemble(&ram(&[0xA7, 0x10], 0), 0).unofficial);
+    assert!(disassemble(&ram(&[0xEB, 0x10], 0), 0).unofficial);
+    assert!(!disassemble(&ram(&[0xE9, 0x10], 0), 0).unofficial);
+}
+
+#[test]
+fn kil_is_one_byte() {
+    let instruction = disassemble(&ram(&[0x02], 0x0200), 0x0200);
+    assert_eq!(instruction.to_string(), "KIL");
+    assert_eq!(instruction.next(), 0x0201);
+}
+
+#[test]
+fn annotates_effective_addresses() {
+    let mut ram = ram(&[0xBD, 0x00, 0x02, 0xB1, 0x10, 0x6C, 0xFF, 0x02, 0x20, 0x00, 0x80], 0x8000);
+    ram.load(&[0x3F], 0x0210);
+    ram.load(&[0xF8, 0x02], 0x0010);
+    ram.load(&[0x77], 0x0308);
+    ram.load(&[0x34], 0x02FF);
+    ram.load(&[0x12], 0x0200);
+
+    let lda = disassemble(&ram, 0x8000);
+    assert_eq!(lda.effective_address(&ram, 0x10, 0), Some(0x0210));
+    assert_eq!(lda.annotation(&ram, 0x10, 0), "@ 0210 = 3F");
+
+    let indirect = disassemble(&ram, lda.next());
+    assert_eq!(indirect.annotation(&ram, 0, 0x10), "= 02F8 @ 0308 = 77");
+
+    // JMP ($02FF) takes its high byte from $0200
+    let jmp = disassemble(&ram, indirect.next());
+    assert_eq!(jmp.annotation(&ram, 0, 0), "= 1234");
+
+    let jsr = disassemble(&ram, jmp.next());
+    assert_eq!(jsr.annotation(&ram, 0, 0), "");
+}
@@ -0,0 +1,169 @@
+// Status flags set by the official instructions, checking the cases that are easy to get wrong
+
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant, FLAG_CARRY, FLAG_ZERO, FLAG_INTERRUPT, FLAG_BREAK, FLAG_UNUSED, FLAG_OVERFLOW,
+               FLAG_NEGATIVE};
+
+// A CPU about to run program from $0200 with the given registers and flags
+fn load(program: &[u8], a: u8, x: u8, y: u8, p: u8) -> CPU<FlatRam> {
+    let mut ram = FlatRam::new();
+    ram.load(program, 0x0200);
+    ram.load(&[0x00, 0x02], 0xFFFC);
+
+    let mut cpu = CPU::with_variant(ram, Variant::Ricoh2A03);
+    let mut state = cpu.state();
+    state.a = a;
+    state.x = x;
+    state.y = y;
+    state.p = p;
+    cpu.set_state(&state);
+    cpu
+}
+
+#[test]
+fn powers_on_with_interrupts_disabled() {
+    let cpu = CPU::with_variant(FlatRam::new(), Variant::Ricoh2A03);
+    assert!(cpu.state().flag(FLAG_INTERRUPT));
+}
+
+#[test]
+fn bit_copies_overflow_and_negative_from_memory() {
+    // BIT $10 with A = $01 and $C0 in memory, the AND is zero but bits 6 and 7 come from memory
+    let mut cpu = load(&[0x24, 0x10], 0x01, 0, 0, 0);
+    cpu.memory.data[0x10] = 0xC0;
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(state.a, 0x01);
+    assert!(state.flag(FLAG_ZERO));
+    assert!(state.flag(FLAG_OVERFLOW));
+    assert!(state.flag(FLAG_NEGATIVE));
+}
+
+#[test]
+fn compares_set_negative_from_the_difference_and_leave_overflow_alone() {
+    // CMP #$01 with A = $00, the difference $FF is negative and there's a borrow
+    let mut cpu = load(&[0xC9, 0x01], 0x00, 0, 0, FLAG_OVERFLOW);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert!(state.flag(FLAG_NEGATIVE));
+    assert!(!state.flag(FLAG_CARRY));
+    assert!(!state.flag(FLAG_ZERO));
+    assert!(state.flag(FLAG_OVERFLOW));
+
+    // CPX #$10 with X = $90, the difference $80 is negative even though X is the larger
+    let mut cpu = load(&[0xE0, 0x10], 0, 0x90, 0, 0);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert!(state.flag(FLAG_NEGATIVE));
+    assert!(state.flag(FLAG_CARRY));
+    assert!(!state.flag(FLAG_OVERFLOW));
+
+    // CPY #$40 with Y = $40
+    let mut cpu = load(&[0xC0, 0x40], 0, 0, 0x40, 0);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert!(state.flag(FLAG_ZERO));
+    assert!(state.flag(FLAG_CARRY));
+    assert!(!state.flag(FLAG_NEGATIVE));
+}
+
+#[test]
+fn eor_sets_zero_and_negative_from_the_accumulator() {
+    // EOR #$FF with A = $0F and Y = 0
+    let mut cpu = load(&[0x49, 0xFF], 0x0F, 0, 0, 0);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(state.a, 0xF0);
+    assert!(state.flag(FLAG_NEGATIVE));
+    assert!(!state.flag(FLAG_ZERO));
+
+    // EOR #$AA with A = $AA and Y = $80
+    let mut cpu = load(&[0x49, 0xAA], 0xAA, 0, 0x80, 0);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(state.a, 0x00);
+    assert!(state.flag(FLAG_ZERO));
+    assert!(!state.flag(FLAG_NEGATIVE));
+}
+
+#[test]
+fn lsr_of_memory_sets_flags_from_the_result() {
+    // LSR $10 with $01 in memory shifts it out to carry and leaves zero, A = $FF mustn't matter
+    let mut cpu = load(&[0x46, 0x10], 0xFF, 0, 0, FLAG_NEGATIVE);
+    cpu.memory.data[0x10] = 0x01;
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(cpu.memory.data[0x10], 0x00);
+    assert!(state.flag(FLAG_ZERO));
+    assert!(state.flag(FLAG_CARRY));
+    assert!(!state.flag(FLAG_NEGATIVE));
+}
+
+#[test]
+fn rotates_of_memory_set_flags_from_the_result() {
+    // ROL $10 with $40 in memory and carry set, A = 0 mustn't matter
+    let mut cpu = load(&[0x26, 0x10], 0x00, 0, 0, FLAG_CARRY);
+    cpu.memory.data[0x10] = 0x40;
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(cpu.memory.data[0x10], 0x81);
+    assert!(state.flag(FLAG_NEGATIVE));
+    assert!(!state.flag(FLAG_ZERO));
+    assert!(!state.flag(FLAG_CARRY));
+
+    // ROR $10 with $01 in memory and carry clear
+    let mut cpu = load(&[0x66, 0x10], 0xFF, 0, 0, 0);
+    cpu.memory.data[0x10] = 0x01;
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(cpu.memory.data[0x10], 0x00);
+    assert!(state.flag(FLAG_ZERO));
+    assert!(state.flag(FLAG_CARRY));
+}
+
+#[test]
+fn sbc_carry_is_the_inverse_of_borrow() {
+    // SBC #$01 with A = $00 and carry set borrows
+    let mut cpu = load(&[0xE9, 0x01], 0x00, 0, 0, FLAG_CARRY);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(state.a, 0xFF);
+    assert!(!state.flag(FLAG_CARRY));
+    assert!(!state.flag(FLAG_OVERFLOW));
+
+    // SBC #$01 with A = $80 goes from negative to positive, which overflows
+    let mut cpu = load(&[0xE9, 0x01], 0x80, 0, 0, FLAG_CARRY);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert_eq!(state.a, 0x7F);
+    assert!(state.flag(FLAG_CARRY));
+    assert!(state.flag(FLAG_OVERFLOW));
+}
+
+#[test]
+fn plp_ignores_the_break_and_unused_bits() {
+    // PLP pulling just the B flag
+    let mut cpu = load(&[0x28], 0, 0, 0, 0);
+    cpu.memory.data[0x01FF] = FLAG_BREAK;
+    let mut state = cpu.state();
+    state.sp = 0xFE;
+    cpu.set_state(&state);
+    cpu.step(Interrupt::IntNone);
+
+    let state = cpu.state();
+    assert!(!state.flag(FLAG_BREAK));
+    assert!(state.flag(FLAG_UNUSED));
+}
@@ -0,0 +1,124 @@
+// Runs the CPU on 64k of plain RAM
+
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant, FLAG_BREAK, FLAG_CARRY, FLAG_DECIMAL, FLAG_INTERRUPT, FLAG_UNUSED};
+
+const START: u16 = 0x0200;
+
+fn cpu(program: &[u8]) -> CPU<FlatRam> {
+    let mut ram = FlatRam::new();
+    ram.load(program, START);
+    ram.load(&[START as u8, (START >> 8) as u8], 0xFFFC);
+
+    CPU::with_variant(ram, Variant::Nmos6502)
+}
+
+#[test]
+fn multiplies_by_shifting_and_adding() {
+    // $10 * $11 into $12 (low) and $13 (high)
+    let mut cpu = cpu(&[
+        0xA9, 0x00,         // LDA #0
+        0x85, 0x13,         // STA $13
+        0xA2, 0x08,         // LDX #8
+        0x46, 0x10,         // loop: LSR $10
+        0x90, 0x03,         // BCC skip
+        0x18,               // CLC
+        0x65, 0x11,         // ADC $11
+        0x6A,               // skip: ROR A
+        0x66, 0x13,         // ROR $13
+        0xCA,               // DEX
+        0xD0, 0xF3,         // BNE loop
+        0x85, 0x12,         // STA $12
+        0x4C, 0x15, 0x02,   // JMP *
+    ]);
+    cpu.memory.data[0x10] = 13;
+    cpu.memory.data[0x11] = 211;
+
+    for _ in 0..100 {
+        cpu.step(Interrupt::IntNone);
+    }
+
+    // The product builds up in A and $13, A ending up as the high byte
+    let product = (cpu.memory.data[0x12] as u16) << 8 | cpu.memory.data[0x13] as u16;
+    assert_eq!(product, 13 * 211);
+}
+
+// Runs a single setup instruction, if there is one, then returns the cycles taken by the
+// instruction after it
+fn cycles(setup: &[u8], instruction: &[u8]) -> u16 {
+    let mut program = setup.to_vec();
+    program.extend_from_slice(instruction);
+    let mut cpu = cpu(&program);
+
+    if !setup.is_empty() {
+        cpu.step(Interrupt::IntNone);
+    }
+
+    cpu.step(Interrupt::IntNone)
+}
+
+#[test]
+fn instruction_timing() {
+    let ldx_1 = [0xA2, 0x01];
+    let ldx_ff = [0xA2, 0xFF];
+
+    // Reads only pay for crossing a page
+    assert_eq!(cycles(&ldx_1, &[0xBD, 0x00, 0x30]), 4);    // LDA $3000,X
+    assert_eq!(cycles(&ldx_ff, &[0xBD, 0x01, 0x30]), 5);   // LDA $3001,X
+    // Writes and read-modify-writes always do
+    assert_eq!(cycles(&ldx_1, &[0x9D, 0x00, 0x30]), 5);    // STA $3000,X
+    assert_eq!(cycles(&ldx_1, &[0xFE, 0x00, 0x30]), 7);    // INC $3000,X
+    assert_eq!(cycles(&[], &[0xE6, 0x10]), 5);              // INC $10
+    assert_eq!(cycles(&[], &[0xA1, 0x10]), 6);              // LDA ($10,X)
+    assert_eq!(cycles(&[], &[0x03, 0x10]), 8);              // SLO ($10,X)
+
+    assert_eq!(cycles(&[], &[0x20, 0x00, 0x30]), 6);        // JSR $3000
+    assert_eq!(cycles(&[], &[0x6C, 0x00, 0x30]), 5);        // JMP ($3000)
+    assert_eq!(cycles(&[], &[0x48]), 3);                    // PHA
+    assert_eq!(cycles(&[], &[0x68]), 4);                    // PLA
+    assert_eq!(cycles(&[], &[0x00]), 7);                    // BRK
+
+    // Branches cost one more when taken and another when the target is on a different page
+    assert_eq!(cycles(&[0x38], &[0x90, 0x10]), 2);          // SEC, BCC not taken
+    assert_eq!(cycles(&[0x18], &[0x90, 0x10]), 3);          // CLC, BCC taken
+    assert_eq!(cycles(&[0x18], &[0x90, 0x80]), 4);          // CLC, BCC taken back to $0183
+}
+
+#[test]
+fn state_after_reset() {
+    let cpu = cpu(&[0xEA]);
+    let state = cpu.state();
+
+    assert_eq!(state.pc, START);
+    assert_eq!(state.sp, 0xFD);
+    assert!(state.flag(FLAG_INTERRUPT));
+    assert_eq!(state.cycles, 7);
+}
+
+#[test]
+fn set_state_is_seen_by_instructions() {
+    // PHP, then TXA and ADC #1 on the registers we put in place
+    let mut cpu = cpu(&[0x08, 0x8A, 0x69, 0x01]);
+
+    let mut state = cpu.state();
+    state.x = 0x41;
+    state.sp = 0x80;
+    state.set_flag(FLAG_CARRY, true);
+    state.set_flag(FLAG_DECIMAL, false);
+    cpu.set_state(&state);
+    assert_eq!(cpu.state(), state);
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.memory.data[0x0180], state.p | FLAG_BREAK | FLAG_UNUSED);
+
+    cpu.step(Interrupt::IntNone);
+    cpu.step(Interrupt::IntNone);
+    let after = cpu.state();
+    assert_eq!(after.a, 0x43);
+    assert_eq!(after.sp, 0x7F);
+    assert_eq!(after.pc, START + 4);
+    assert!(!after.flag(FLAG_CARRY));
+    assert_eq!(after.cycles, state.cycles + 3 + 2 + 2);
+}
@@ -0,0 +1,166 @@
+// Talks to the gdb stub over a local socket while it runs nestest.nes in its automation mode,
+// which starts C000 JMP $C5F5 ... C5FD JSR $C72D
+
+extern crate nes;
+
+mod common;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use nes::cpu::Interrupt;
+use nes::debugger::gdb::{Connection, GdbStub};
+
+// Just enough of a client to send a packet and read the reply
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    // Gives up on a reply that never comes rather than hanging the test
+    fn connect(address: SocketAddr) -> Client {
+        let stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        Client { stream: stream }
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.reply(packet)
+    }
+
+    fn send(&mut self, packet: &str) {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", packet, checksum).unwrap();
+    }
+
+    // Reads the ack for packet and the reply that follows
+    fn reply(&mut self, packet: &str) -> String {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+', "{} wasn't acknowledged", packet);
+
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'$');
+
+        let mut reply = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+#[test]
+fn serves_a_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(address);
+        let mut replies = Vec::new();
+        for packet in &["qSupported:swbreak+", "?", "g", "m c000,3", "M 10,2:abcd", "m 10,2", "M 8000,1:00",
+                        "M 7fff,2:1122", "m 7fff,1",
+                        "Z0,c5fd,1", "c", "p5", "s", "p5", "P0=42", "p0", "z0,c5fd,1",
+                        "Z2,0,10001", "Z2,0,10000", "z2,0,10000", "Z2,0200,1", "c", "bogus"] {
+            replies.push(client.request(&packet.replace(' ', "")));
+        }
+
+        write!(client.stream, "$k#6b").unwrap();
+        replies
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut stub = GdbStub::new(stream);
+    let mut cpu = common::nestest_automation();
+    stub.debugger.attach(&mut cpu);
+
+    let mut connection = Connection::Open;
+    for _ in 0..100000 {
+        connection = stub.check(&mut cpu).unwrap();
+        if connection != Connection::Open {
+            break;
+        }
+        cpu.step(Interrupt::IntNone);
+    }
+    assert_eq!(connection, Connection::Killed);
+
+    let replies = client.join().unwrap();
+    let expected = [
+        "PacketSize=1000;QStartNoAckMode+",
+        "S05",
+        "00000024fd00c0",
+        "4cf5c5",
+        "OK",
+        "abcd",
+        // ROM can't be written, and a write that runs into it changes nothing
+        "E01",
+        "E01",
+        "00",
+        "OK",
+        "S05",
+        "fdc5",
+        "S05",
+        "2dc7",
+        "OK",
+        "42",
+        "OK",
+        // Watchpoints can cover the whole address space but no more
+        "E01",
+        "OK",
+        "OK",
+        "OK",
+        "T05watch:0200;",
+        "",
+    ];
+    assert_eq!(replies, expected);
+}
+
+#[test]
+fn keeps_packets_that_arrive_while_running() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(address);
+        let mut replies = vec![client.request("Z0,c5fd,1")];
+
+        // The second packet is waiting by the time the stub polls between instructions
+        client.send("c");
+        client.send("p5");
+        replies.push(client.reply("c"));
+        replies.push(client.reply("p5"));
+
+        write!(client.stream, "$k#6b").unwrap();
+        replies
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut stub = GdbStub::new(stream);
+    let mut cpu = common::nestest_automation();
+    stub.debugger.attach(&mut cpu);
+
+    let mut connection = Connection::Open;
+    for _ in 0..100000 {
+        connection = stub.check(&mut cpu).unwrap();
+        if connection != Connection::Open {
+            break;
+        }
+        cpu.step(Interrupt::IntNone);
+        stub.poll().unwrap();
+    }
+    assert_eq!(connection, Connection::Killed);
+
+    assert_eq!(client.join().unwrap(), ["OK", "S05", "fdc5"]);
+}
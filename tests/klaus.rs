@@ -0,0 +1,58 @@
+// Klaus Dormann's 6502 functional test, run on plain RAM. The binary isn't distributed with
+// the repo, build 6502_functional_test.bin with decimal mode tests enabled and drop it in
+// tests/roms/ or point KLAUS_ROM at it, then run cargo test -- --ignored.
+//
+// The test traps by jumping to itself, where it traps at SUCCESS_PC everything passed.
+
+extern crate nes;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant};
+
+const START: u16 = 0x0400;
+const SUCCESS_PC: u16 = 0x3469;
+const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+fn rom_path() -> PathBuf {
+    match env::var("KLAUS_ROM") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms/6502_functional_test.bin"),
+    }
+}
+
+#[test]
+#[ignore = "needs 6502_functional_test.bin in tests/roms or KLAUS_ROM"]
+fn functional_test() {
+    let path = rom_path();
+    let mut rom = Vec::new();
+    match File::open(&path) {
+        Ok(mut file) => { file.read_to_end(&mut rom).unwrap(); },
+        Err(err) => panic!("couldn't open {}: {}", path.display(), err),
+    }
+
+    let mut ram = FlatRam::new();
+    ram.load(&rom, 0);
+    let mut cpu = CPU::with_variant(ram, Variant::Nmos6502);
+    cpu.set_pc(START);
+
+    let success = match env::var("KLAUS_SUCCESS") {
+        Ok(pc) => u16::from_str_radix(pc.trim_start_matches("0x"), 16).unwrap(),
+        Err(_) => SUCCESS_PC,
+    };
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        let pc = cpu.pc();
+        cpu.step(Interrupt::IntNone);
+        if cpu.pc() == pc {
+            assert_eq!(pc, success, "trapped at {:04X}, {:?}", pc, cpu.state());
+            return;
+        }
+    }
+
+    panic!("no trap after {} instructions, {:?}", MAX_INSTRUCTIONS, cpu.state());
+}
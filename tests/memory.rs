@@ -0,0 +1,105 @@
+// The CPU memory map on an NROM cartridge, nestest.nes, OAM DMA and the PPU's nametable mirroring
+
+extern crate nes;
+
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::{cartridge, memory_map};
+use nes::cartridge::Cartridge;
+use nes::cpu::{CPU, Interrupt};
+use nes::mapper::Mirroring;
+use nes::memory::PPUMemoryMap;
+
+#[test]
+fn ignores_writes_to_the_expansion_area_and_rom() {
+    let mut memory = memory_map(common::nestest());
+    let rom = memory.peek(0xC000);
+
+    memory.write(0x12, 0x4020);
+    memory.write(0x12, 0x5FFF);
+    memory.write(0x12, 0xC000);
+
+    assert_eq!(memory.peek(0xC000), rom);
+
+    // PRG-RAM still takes writes
+    memory.write(0x12, 0x6000);
+    assert_eq!(memory.peek(0x6000), 0x12);
+}
+
+// Runs STA $4014 from internal RAM with the cycle counter at start, copying page $03 into OAM.
+// Returns what the step took and how much of it was stalled.
+fn oam_dma(start: u64) -> (u16, u16) {
+    let mut cpu = CPU::new(memory_map(cartridge(0, 0x00)));
+
+    cpu.memory.ram[0x200..0x203].copy_from_slice(&[0x8D, 0x14, 0x40]);
+    for i in 0..256 {
+        cpu.memory.ram[0x300 + i] = i as u8 ^ 0xA5;
+    }
+
+    let mut state = cpu.state();
+    state.pc = 0x0200;
+    state.a = 0x03;
+    state.cycles = start;
+    cpu.set_state(&state);
+
+    let cycles = cpu.step(Interrupt::IntNone);
+    for i in 0..256 {
+        assert_eq!(cpu.memory.ppu.oam[i], i as u8 ^ 0xA5);
+    }
+    assert_eq!(cpu.cycles(), start + cycles as u64);
+    (cycles, cpu.stall())
+}
+
+#[test]
+fn oam_dma_waits_for_an_even_cycle_to_start() {
+    // STA abs writes on its fourth cycle, which is cycle 3 counting from 0. A write on an odd
+    // cycle costs an extra cycle of alignment.
+    assert_eq!(oam_dma(0), (4 + 514, 514));
+    assert_eq!(oam_dma(1), (4 + 513, 513));
+}
+
+// Writes through each of the four nametables and their mirrors at $3000 and checks that the
+// byte reads back through exactly the nametables that share its physical table. physical maps
+// each nametable to 2k of PPU vram (0, 1) or the cartridge's extra 2k (2, 3).
+fn check_mirroring(cartridge: Rc<RefCell<Cartridge>>, mirroring: Mirroring, physical: [usize; 4]) {
+    assert_eq!(cartridge.borrow().mirroring(), mirroring);
+    let mut memory = PPUMemoryMap::new(cartridge.clone());
+
+    for table in 0..4 {
+        for &(base, tag) in &[(0x2000, 0x00), (0x3000, 0x10)] {
+            let value = tag | (table as u8 + 1);
+            memory.write(value, base + table as u16 * 0x400 + 0x123);
+
+            for other in 0..4 {
+                for &mirror in &[0x2000, 0x3000] {
+                    let address = mirror + other as u16 * 0x400 + 0x123;
+                    let shared = physical[other] == physical[table];
+                    assert_eq!(memory.read(address) == value, shared,
+                               "{:?}: wrote {:02X} to nametable {}, read {:04X}", mirroring, value, table, address);
+                }
+            }
+
+            let offset = (physical[table] % 2) * 0x400 + 0x123;
+            let stored = if physical[table] < 2 { memory.vram[offset] } else { cartridge.borrow().vram[offset] };
+            assert_eq!(stored, value, "{:?}: nametable {} stored in the wrong place", mirroring, table);
+        }
+    }
+}
+
+#[test]
+fn nametables_mirror_as_the_cartridge_says() {
+    check_mirroring(cartridge(0, 0x00), Mirroring::Horizontal, [0, 0, 1, 1]);
+    check_mirroring(cartridge(0, 0x01), Mirroring::Vertical, [0, 1, 0, 1]);
+
+    // Four-screen boards add 2k of VRAM for the third and fourth nametables
+    check_mirroring(cartridge(0, 0x08), Mirroring::FourScreen, [0, 1, 2, 3]);
+
+    // AxROM picks one nametable for all four with bit 4 of its register
+    check_mirroring(cartridge(7, 0x00), Mirroring::SingleScreenLower, [0, 0, 0, 0]);
+    let upper = cartridge(7, 0x00);
+    upper.borrow_mut().write(0x10, 0x8000);
+    check_mirroring(upper, Mirroring::SingleScreenUpper, [1, 1, 1, 1]);
+}
@@ -0,0 +1,38 @@
+// Runs nestest.nes in its automation mode, starting at $C000 rather than the reset vector, and
+// compares a Nintendulator style trace of every instruction against tests/nestest.log
+
+extern crate nes;
+
+mod common;
+
+use std::env;
+use std::path::Path;
+
+use nes::cpu::Interrupt;
+
+#[test]
+fn nestest_matches_golden_log() {
+    let log = common::read_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("nestest.log"));
+    let log = String::from_utf8(log).unwrap();
+
+    let mut cpu = common::nestest_automation();
+
+    let mut previous = String::new();
+    for (number, expected) in log.lines().enumerate() {
+        let line = cpu.trace();
+        if env::var("NESTEST_PRINT").is_ok() {
+            println!("{}", line);
+        }
+
+        assert!(line == expected,
+                "trace diverged at line {}\nprevious: {}\nexpected: {}\n   found: {}",
+                number + 1, previous, expected, line);
+
+        cpu.step(Interrupt::IntNone);
+        previous = line;
+    }
+
+    // Official and unofficial opcode results, 0 when everything passed
+    assert_eq!(cpu.memory.read(0x02), 0);
+    assert_eq!(cpu.memory.read(0x03), 0);
+}
@@ -0,0 +1,104 @@
+extern crate nes;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant};
+use nes::trace::Tracer;
+
+// A log we can still read after handing it to the CPU
+#[derive(Clone)]
+struct SharedLog(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedLog {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+    }
+}
+
+// LDX #3, loop: DEX, BNE loop, JMP *
+fn cpu() -> CPU<FlatRam> {
+    let mut ram = FlatRam::new();
+    ram.load(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0x4C, 0x05, 0x02], 0x0200);
+    ram.load(&[0x00, 0x02], 0xFFFC);
+
+    CPU::with_variant(ram, Variant::Nmos6502)
+}
+
+fn run(cpu: &mut CPU<FlatRam>, tracer: Tracer, steps: usize) {
+    cpu.set_tracer(Some(tracer));
+    for _ in 0..steps {
+        cpu.step(Interrupt::IntNone);
+    }
+}
+
+#[test]
+fn logs_every_instruction() {
+    let log = SharedLog(Rc::new(RefCell::new(Vec::new())));
+    let mut cpu = cpu();
+    run(&mut cpu, Tracer::new(log.clone()), 8);
+
+    let lines = log.lines();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[0], "0200  A2 03     LDX #$03                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  0 CYC:7");
+    assert!(lines[1].starts_with("0202  CA        DEX "));
+    assert!(lines[2].starts_with("0203  D0 FD     BNE $0202 "));
+    assert!(lines[7].starts_with("0205  4C 05 02  JMP $0205 "));
+}
+
+#[test]
+fn filters_by_pc() {
+    let log = SharedLog(Rc::new(RefCell::new(Vec::new())));
+    let mut tracer = Tracer::new(log.clone());
+    tracer.pcs = Some(0x0202..=0x0202);
+
+    let mut cpu = cpu();
+    run(&mut cpu, tracer, 8);
+
+    let lines = log.lines();
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|line| line.starts_with("0202 ")));
+}
+
+#[test]
+fn filters_by_frame() {
+    // Plain RAM has no video so everything happens in frame 0
+    let log = SharedLog(Rc::new(RefCell::new(Vec::new())));
+    let mut tracer = Tracer::new(log.clone());
+    tracer.frames = Some(1..=10);
+
+    let mut cpu = cpu();
+    run(&mut cpu, tracer, 8);
+    assert!(log.lines().is_empty());
+}
+
+#[test]
+fn can_be_paused_and_removed() {
+    let log = SharedLog(Rc::new(RefCell::new(Vec::new())));
+    let mut cpu = cpu();
+    run(&mut cpu, Tracer::new(log.clone()), 2);
+
+    cpu.tracer().unwrap().enabled = false;
+    cpu.step(Interrupt::IntNone);
+    cpu.tracer().unwrap().enabled = true;
+    cpu.step(Interrupt::IntNone);
+
+    assert!(cpu.set_tracer(None).is_some());
+    cpu.step(Interrupt::IntNone);
+
+    let lines = log.lines();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].starts_with("0202 "));
+}
@@ -0,0 +1,151 @@
+// Address calculations and the stack wrap around the way they do on a real 6502
+
+extern crate nes;
+
+use nes::bus::FlatRam;
+use nes::cpu::{CPU, Interrupt, Variant};
+
+// A CPU about to run program at address with the given index registers and stack pointer
+fn cpu(program: &[u8], address: u16, x: u8, y: u8, sp: u8) -> CPU<FlatRam> {
+    let mut ram = FlatRam::new();
+    ram.load(program, address);
+    ram.load(&[address as u8, (address >> 8) as u8], 0xFFFC);
+
+    let mut cpu = CPU::with_variant(ram, Variant::Nmos6502);
+    let mut state = cpu.state();
+    state.x = x;
+    state.y = y;
+    state.sp = sp;
+    cpu.set_state(&state);
+    cpu
+}
+
+#[test]
+fn zero_page_x_stays_in_zero_page() {
+    // LDA $F0,X reads $0010, not $0110
+    let mut cpu = cpu(&[0xB5, 0xF0], 0x0200, 0x20, 0, 0xFD);
+    cpu.memory.data[0x0010] = 0x11;
+    cpu.memory.data[0x0110] = 0x22;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x11);
+}
+
+#[test]
+fn zero_page_y_stays_in_zero_page() {
+    // STX $FF,Y writes $0000
+    let mut cpu = cpu(&[0x96, 0xFF], 0x0200, 0x5A, 0x01, 0xFD);
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.memory.data[0x0000], 0x5A);
+    assert_eq!(cpu.memory.data[0x0100], 0x00);
+}
+
+#[test]
+fn indexed_indirect_pointer_wraps() {
+    // LDA ($FE,X) with X = 1 takes the pointer from $FF and $00
+    let mut cpu = cpu(&[0xA1, 0xFE], 0x0200, 0x01, 0, 0xFD);
+    cpu.memory.data[0x00FF] = 0x34;
+    cpu.memory.data[0x0000] = 0x12;
+    cpu.memory.data[0x0100] = 0x56;
+    cpu.memory.data[0x1234] = 0x77;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x77);
+}
+
+#[test]
+fn indirect_indexed_pointer_wraps() {
+    // LDA ($FF),Y takes the pointer from $FF and $00 before adding Y
+    let mut cpu = cpu(&[0xB1, 0xFF], 0x0200, 0, 0x10, 0xFD);
+    cpu.memory.data[0x00FF] = 0xF8;
+    cpu.memory.data[0x0000] = 0x12;
+    cpu.memory.data[0x1308] = 0x77;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x77);
+}
+
+#[test]
+fn absolute_indexed_wraps_past_the_top_of_memory() {
+    // LDA $FFF0,X reads $0010
+    let mut cpu = cpu(&[0xBD, 0xF0, 0xFF], 0x0200, 0x20, 0, 0xFD);
+    cpu.memory.data[0x0010] = 0x77;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x77);
+}
+
+#[test]
+fn jmp_indirect_does_not_cross_pages() {
+    // JMP ($02FF) takes the high byte from $0200, not $0300
+    let mut cpu = cpu(&[0x6C, 0xFF, 0x02], 0x0200, 0, 0, 0xFD);
+    cpu.memory.data[0x02FF] = 0x34;
+    cpu.memory.data[0x0300] = 0x56;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().pc, 0x6C34);
+}
+
+#[test]
+fn push_wraps_to_the_top_of_the_stack() {
+    // PHA, PHA with SP = 0 writes $0100 and then $01FF
+    let mut cpu = cpu(&[0x48, 0x48], 0x0200, 0, 0, 0x00);
+    let mut state = cpu.state();
+    state.a = 0x77;
+    cpu.set_state(&state);
+
+    cpu.step(Interrupt::IntNone);
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.memory.data[0x0100], 0x77);
+    assert_eq!(cpu.memory.data[0x01FF], 0x77);
+    assert_eq!(cpu.state().sp, 0xFE);
+}
+
+#[test]
+fn pull_wraps_to_the_bottom_of_the_stack() {
+    // PLA with SP = $FF reads $0100
+    let mut cpu = cpu(&[0x68], 0x0200, 0, 0, 0xFF);
+    cpu.memory.data[0x0100] = 0x77;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x77);
+    assert_eq!(cpu.state().sp, 0x00);
+}
+
+#[test]
+fn jsr_and_rts_across_the_stack_boundary() {
+    // JSR $0300 with SP = 0 pushes the return address to $0100 and $01FF, RTS brings it back
+    let mut cpu = cpu(&[0x20, 0x00, 0x03], 0x0200, 0, 0, 0x00);
+    cpu.memory.data[0x0300] = 0x60;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().pc, 0x0300);
+    assert_eq!(cpu.memory.data[0x0100], 0x02);
+    assert_eq!(cpu.memory.data[0x01FF], 0x02);
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().pc, 0x0203);
+    assert_eq!(cpu.state().sp, 0x00);
+}
+
+#[test]
+fn operands_wrap_past_the_top_of_memory() {
+    // LDA $1234 at $FFFE takes its high byte from $0000, and the next instruction is at $0001
+    let mut cpu = cpu(&[0xAD, 0x34], 0xFFFE, 0, 0, 0xFD);
+    cpu.memory.data[0x0000] = 0x12;
+    cpu.memory.data[0x1234] = 0x77;
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().a, 0x77);
+    assert_eq!(cpu.state().pc, 0x0001);
+}
+
+#[test]
+fn branches_wrap_past_the_top_of_memory() {
+    // BCC +$10 at $FFF0 lands on $0002
+    let mut cpu = cpu(&[0x90, 0x10], 0xFFF0, 0, 0, 0xFD);
+
+    cpu.step(Interrupt::IntNone);
+    assert_eq!(cpu.state().pc, 0x0002);
+}